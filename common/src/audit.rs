@@ -0,0 +1,48 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::RouteReadWriteError;
+
+/// ルートに許可されていないリポジトリから届いたので拒否したWebhookの記録
+///
+/// CloudWatchのログはすぐに消えるので、あとから調べられるようにテーブルに残す
+pub struct RejectedWebhook<'s> {
+    pub route_id: &'s str,
+    pub expected_repository: &'s str,
+    pub actual_repository: &'s str,
+    pub event_name: &'s str,
+    pub delivery_id: Option<&'s str>,
+    /// pingにはsenderがないので、代わりにWebhookのIDで送り元を特定する
+    pub sender: Option<&'s str>,
+    pub hook_id: Option<u64>,
+}
+impl RejectedWebhook<'_> {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-AuditLog";
+
+    pub async fn record(&self, client: &aws_sdk_dynamodb::Client) -> Result<(), RouteReadWriteError> {
+        let now = time::OffsetDateTime::now_utc();
+        let mut req = client
+            .put_item()
+            .table_name(Self::TABLE_NAME)
+            .item("route_id", AttributeValue::S(self.route_id.into()))
+            // 同じ秒に複数届いても上書きしないようにナノ秒まで持つ
+            .item("rejected_at", AttributeValue::N(now.unix_timestamp_nanos().to_string()))
+            .item(
+                "expected_repository",
+                AttributeValue::S(self.expected_repository.into()),
+            )
+            .item("actual_repository", AttributeValue::S(self.actual_repository.into()))
+            .item("event", AttributeValue::S(self.event_name.into()));
+        if let Some(id) = self.delivery_id {
+            req = req.item("delivery_id", AttributeValue::S(id.into()));
+        }
+        if let Some(s) = self.sender {
+            req = req.item("sender", AttributeValue::S(s.into()));
+        }
+        if let Some(id) = self.hook_id {
+            req = req.item("hook_id", AttributeValue::N(id.to_string()));
+        }
+        req.send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+}
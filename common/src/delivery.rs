@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+
+use crate::RouteReadWriteError;
+
+/// Webhook配信(X-GitHub-Delivery)の処理状況の記録
+///
+/// GitHubの再送で同じ配信が複数回届いても、通知先ごとに一度だけ投稿するために使う
+pub struct WebhookDelivery;
+impl WebhookDelivery {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-Deliveries";
+    /// 記録を残しておく期間(GitHubから再送できるのは3日以内の配信だけ)
+    const RETENTION: time::Duration = time::Duration::days(3);
+    /// 処理中の記録が有効な期間。Lambdaのタイムアウトより長くしておき、
+    /// 途中で止められた実行の記録はこれを過ぎたら再送で取り直せるようにする
+    const LEASE: time::Duration = time::Duration::seconds(60);
+
+    const STATUS_IN_PROGRESS: &'static str = "in_progress";
+    const STATUS_DONE: &'static str = "done";
+
+    /// 配信の処理を始める
+    ///
+    /// 処理済みか、ほかの実行が処理中ならNone。以前の実行が途中で失敗していた場合は、
+    /// その時点で投稿済みだったもの(`mark_delivered` で記録したキー)を返す
+    pub async fn claim(
+        client: &aws_sdk_dynamodb::Client,
+        delivery_id: &str,
+        event_name: Option<&str>,
+    ) -> Result<Option<HashSet<String>>, RouteReadWriteError> {
+        let now = time::OffsetDateTime::now_utc();
+        let mut req = client
+            .update_item()
+            .table_name(Self::TABLE_NAME)
+            .key("delivery_id", AttributeValue::S(delivery_id.into()))
+            .condition_expression(
+                "attribute_not_exists(delivery_id) OR (#status = :in_progress AND lease_expires_at < :now)",
+            )
+            .expression_attribute_names("#status", "status")
+            .expression_attribute_values(":in_progress", AttributeValue::S(Self::STATUS_IN_PROGRESS.into()))
+            .expression_attribute_values(":now", AttributeValue::N(now.unix_timestamp().to_string()))
+            .expression_attribute_values(
+                ":lease",
+                AttributeValue::N((now + Self::LEASE).unix_timestamp().to_string()),
+            )
+            .expression_attribute_values(
+                ":expires_at",
+                AttributeValue::N((now + Self::RETENTION).unix_timestamp().to_string()),
+            )
+            .return_values(ReturnValue::AllNew);
+        req = match event_name {
+            Some(e) => req
+                .update_expression(
+                    "SET #status = :in_progress, lease_expires_at = :lease, expires_at = if_not_exists(expires_at, :expires_at), event = :event",
+                )
+                .expression_attribute_values(":event", AttributeValue::S(e.into())),
+            None => req.update_expression(
+                "SET #status = :in_progress, lease_expires_at = :lease, expires_at = if_not_exists(expires_at, :expires_at)",
+            ),
+        };
+
+        match req.send().await {
+            Ok(r) => {
+                let delivered = match r.attributes.and_then(|mut a| a.remove("delivered")) {
+                    Some(AttributeValue::Ss(xs)) => xs.into_iter().collect(),
+                    Some(_) => return Err(RouteReadWriteError::ValueIsNotStringSet("delivered")),
+                    None => HashSet::new(),
+                };
+
+                Ok(Some(delivered))
+            }
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(aws_sdk_dynamodb::Error::from(e).into()),
+        }
+    }
+
+    /// 1つの通知先への投稿が済んだことを記録する
+    pub async fn mark_delivered(
+        client: &aws_sdk_dynamodb::Client,
+        delivery_id: &str,
+        key: &str,
+    ) -> Result<(), RouteReadWriteError> {
+        client
+            .update_item()
+            .table_name(Self::TABLE_NAME)
+            .key("delivery_id", AttributeValue::S(delivery_id.into()))
+            .update_expression("ADD delivered :key")
+            .expression_attribute_values(":key", AttributeValue::Ss(vec![key.into()]))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// すべての通知先への投稿が済んだ。以降の再送は落とす
+    pub async fn complete(client: &aws_sdk_dynamodb::Client, delivery_id: &str) -> Result<(), RouteReadWriteError> {
+        client
+            .update_item()
+            .table_name(Self::TABLE_NAME)
+            .key("delivery_id", AttributeValue::S(delivery_id.into()))
+            .update_expression("SET #status = :done REMOVE lease_expires_at")
+            .expression_attribute_names("#status", "status")
+            .expression_attribute_values(":done", AttributeValue::S(Self::STATUS_DONE.into()))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// 処理に失敗したときに処理中の記録を手放して、再送ですぐに処理し直せるようにする
+    ///
+    /// 投稿済みの通知先の記録は残すので、再送ではまだ届いていない通知先にだけ投稿する
+    pub async fn release(client: &aws_sdk_dynamodb::Client, delivery_id: &str) -> Result<(), RouteReadWriteError> {
+        client
+            .update_item()
+            .table_name(Self::TABLE_NAME)
+            .key("delivery_id", AttributeValue::S(delivery_id.into()))
+            .update_expression("SET lease_expires_at = :zero")
+            .condition_expression("#status = :in_progress")
+            .expression_attribute_names("#status", "status")
+            .expression_attribute_values(":in_progress", AttributeValue::S(Self::STATUS_IN_PROGRESS.into()))
+            .expression_attribute_values(":zero", AttributeValue::N(String::from("0")))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! 承認待ちデプロイのSlack上での承認/却下ボタンまわりの共通定義
+
+pub const CALLBACK_ID: &str = "pending_deployment_review";
+pub const ACTION_APPROVE: &str = "approve";
+pub const ACTION_REJECT: &str = "reject";
+
+/// ボタンのvalueに埋め込むレビュー対象
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ReviewTarget {
+    pub repository: String,
+    pub run_id: u64,
+    pub environment: String,
+}
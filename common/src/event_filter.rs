@@ -0,0 +1,145 @@
+/// ルートで受け取るイベントの種類(GitHubのイベント名に対応)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Issues,
+    IssueComment,
+    PullRequest,
+    PullRequestReview,
+    PullRequestReviewComment,
+    Discussion,
+    DiscussionComment,
+    /// デプロイの承認待ちはworkflow_jobで届く
+    WorkflowJob,
+    WorkflowRun,
+    Release,
+    Push,
+}
+impl EventKind {
+    /// 通知できるイベントすべて
+    pub const ALL: &'static [Self] = &[
+        Self::Issues,
+        Self::IssueComment,
+        Self::PullRequest,
+        Self::PullRequestReview,
+        Self::PullRequestReviewComment,
+        Self::Discussion,
+        Self::DiscussionComment,
+        Self::WorkflowJob,
+        Self::WorkflowRun,
+        Self::Release,
+        Self::Push,
+    ];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Issues => "issues",
+            Self::IssueComment => "issue_comment",
+            Self::PullRequest => "pull_request",
+            Self::PullRequestReview => "pull_request_review",
+            Self::PullRequestReviewComment => "pull_request_review_comment",
+            Self::Discussion => "discussion",
+            Self::DiscussionComment => "discussion_comment",
+            Self::WorkflowJob => "workflow_job",
+            Self::WorkflowRun => "workflow_run",
+            Self::Release => "release",
+            Self::Push => "push",
+        }
+    }
+}
+impl std::str::FromStr for EventKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "issues" => Ok(Self::Issues),
+            "issue_comment" => Ok(Self::IssueComment),
+            "pull_request" => Ok(Self::PullRequest),
+            "pull_request_review" => Ok(Self::PullRequestReview),
+            "pull_request_review_comment" => Ok(Self::PullRequestReviewComment),
+            "discussion" => Ok(Self::Discussion),
+            "discussion_comment" => Ok(Self::DiscussionComment),
+            "workflow_job" => Ok(Self::WorkflowJob),
+            "workflow_run" => Ok(Self::WorkflowRun),
+            "release" => Ok(Self::Release),
+            "push" => Ok(Self::Push),
+            _ => Err(()),
+        }
+    }
+}
+
+/// `kind` もしくは `kind:action` の形で書くフィルタの1要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventFilterRule {
+    pub kind: EventKind,
+    /// Noneならそのイベントのアクションすべて
+    pub action: Option<String>,
+}
+impl EventFilterRule {
+    pub fn matches(&self, kind: EventKind, action: Option<&str>) -> bool {
+        self.kind == kind && self.action.as_deref().is_none_or(|a| Some(a) == action)
+    }
+}
+impl std::str::FromStr for EventFilterRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (kind, action) = match s.split_once(':') {
+            Some((k, a)) if !a.is_empty() => (k, Some(a.to_owned())),
+            Some(_) => return Err(()),
+            None => (s, None),
+        };
+
+        Ok(Self {
+            kind: kind.parse()?,
+            action,
+        })
+    }
+}
+impl std::fmt::Display for EventFilterRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.action {
+            Some(ref a) => write!(f, "{}:{a}", self.kind.as_str()),
+            None => f.write_str(self.kind.as_str()),
+        }
+    }
+}
+
+/// ルートに流すイベントの絞り込み
+///
+/// ルールが空のときはすべてのイベントを通す
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EventFilter {
+    pub rules: Vec<EventFilterRule>,
+}
+impl EventFilter {
+    pub fn allows(&self, kind: EventKind, action: Option<&str>) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r.matches(kind, action))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// フィルタを通過しうるイベントの種類
+    pub fn kinds(&self) -> Vec<EventKind> {
+        EventKind::ALL
+            .iter()
+            .copied()
+            .filter(|k| self.rules.is_empty() || self.rules.iter().any(|r| r.kind == *k))
+            .collect()
+    }
+}
+/// `issues,pull_request:opened,push` のようなカンマ区切りの指定。`all` ですべて通す
+impl std::str::FromStr for EventFilter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        if s == "all" {
+            return Ok(Self::default());
+        }
+
+        Ok(Self {
+            rules: s.split(',').map(|r| r.trim().parse()).collect::<Result<_, _>>()?,
+        })
+    }
+}
@@ -0,0 +1,1179 @@
+use std::borrow::Cow;
+
+fn default_bool_false() -> bool {
+    false
+}
+
+#[derive(serde::Deserialize)]
+pub struct User<'s> {
+    #[serde(borrow = "'s")]
+    pub login: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub avatar_url: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+}
+#[derive(serde::Deserialize)]
+pub struct Label<'s> {
+    #[serde(borrow = "'s")]
+    pub name: &'s str,
+}
+/// PRのissueにだけ付く(付いているかどうかしか見ない)
+#[derive(serde::Deserialize)]
+pub struct IssuePullRequestInfo {}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueState {
+    Open,
+    Closed,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Issue<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    pub number: usize,
+    #[serde(borrow = "'s")]
+    pub title: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub user: User<'s>,
+    #[serde(borrow = "'s")]
+    pub labels: Vec<Label<'s>>,
+    #[serde(borrow = "'s")]
+    pub body: Option<Cow<'s, str>>,
+    pub state: IssueState,
+    pub pull_request: Option<IssuePullRequestInfo>,
+}
+impl<'s> Issue<'s> {
+    #[inline(always)]
+    pub fn is_pr(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.state == IssueState::Closed
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct Comment<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub body: Cow<'s, str>,
+    // 以下はpull_request_review_commentのときだけ
+    #[serde(borrow = "'s")]
+    pub path: Option<Cow<'s, str>>,
+    #[serde(borrow = "'s")]
+    pub diff_hunk: Option<Cow<'s, str>>,
+    pub line: Option<u64>,
+    pub start_line: Option<u64>,
+    pub original_line: Option<u64>,
+    pub original_start_line: Option<u64>,
+}
+impl Comment<'_> {
+    /// (start, end)
+    pub fn line_range(&self) -> Option<(u64, u64)> {
+        let (start, end) = match self.line {
+            Some(l) => (self.start_line, l),
+            None => (self.original_start_line, self.original_line?),
+        };
+
+        Some((start.unwrap_or(end), end))
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Review<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub user: User<'s>,
+    #[serde(borrow = "'s")]
+    pub body: Option<Cow<'s, str>>,
+    pub state: ReviewState,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Repository<'s> {
+    #[serde(borrow = "'s")]
+    pub full_name: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+}
+#[derive(serde::Deserialize)]
+pub struct RefExt<'s> {
+    #[serde(borrow = "'s")]
+    pub label: Cow<'s, str>,
+    #[serde(rename = "ref", borrow = "'s")]
+    pub git_ref: Cow<'s, str>,
+}
+#[derive(serde::Deserialize)]
+pub struct PullRequest<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    pub number: usize,
+    #[serde(borrow = "'s")]
+    pub title: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub user: User<'s>,
+    #[serde(borrow = "'s")]
+    pub body: Option<Cow<'s, str>>,
+    #[serde(borrow = "'s")]
+    pub head: RefExt<'s>,
+    #[serde(borrow = "'s")]
+    pub base: RefExt<'s>,
+    pub merged: Option<bool>,
+    #[serde(borrow = "'s")]
+    pub merged_by: Option<User<'s>>,
+    #[serde(default = "default_bool_false")]
+    pub draft: bool,
+    pub state: IssueState,
+    #[serde(borrow = "'s")]
+    pub labels: Vec<Label<'s>>,
+}
+impl PullRequest<'_> {
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.state == IssueState::Closed
+    }
+}
+#[derive(serde::Deserialize)]
+pub struct PullRequestFlags {
+    pub merged: bool,
+    #[serde(default = "default_bool_false")]
+    pub draft: bool,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscussionState {
+    Open,
+    Closed,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Discussion<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    pub number: usize,
+    #[serde(borrow = "'s")]
+    pub title: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub user: User<'s>,
+    pub state: DiscussionState,
+    #[serde(borrow = "'s")]
+    pub body: Option<Cow<'s, str>>,
+}
+impl Discussion<'_> {
+    #[inline(always)]
+    pub fn is_closed(&self) -> bool {
+        self.state == DiscussionState::Closed
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowJob<'s> {
+    pub run_url: &'s str,
+    pub workflow_name: &'s str,
+    pub name: &'s str,
+    pub head_sha: &'s str,
+    pub head_branch: &'s str,
+    pub run_id: u64,
+}
+impl WorkflowJob<'_> {
+    pub async fn run_details(&self) -> reqwest::Result<WorkflowRun> {
+        ApiClient::unauthorized_get_request(self.run_url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?
+            .json()
+            .await
+    }
+}
+
+pub fn workflow_run_html_url(job: &WorkflowJob, repository: &Repository) -> String {
+    format!(
+        "https://github.com/{}/actions/runs/{}",
+        repository.full_name, job.run_id
+    )
+}
+
+pub fn commit_html_url(repository: &Repository, sha: &str) -> String {
+    format!("https://github.com/{}/commit/{}", repository.full_name, sha)
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeploymentInfo<'s> {
+    pub environment: &'s str,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReleaseAsset<'s> {
+    #[serde(borrow = "'s")]
+    pub name: Cow<'s, str>,
+    pub size: u64,
+    #[serde(borrow = "'s")]
+    pub browser_download_url: Cow<'s, str>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Release<'s> {
+    #[serde(borrow = "'s")]
+    pub html_url: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub tag_name: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub name: Option<Cow<'s, str>>,
+    #[serde(borrow = "'s")]
+    pub body: Option<Cow<'s, str>>,
+    #[serde(borrow = "'s")]
+    pub author: User<'s>,
+    pub prerelease: bool,
+    #[serde(borrow = "'s")]
+    pub assets: Vec<ReleaseAsset<'s>>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CommitAuthor<'s> {
+    #[serde(borrow = "'s")]
+    pub name: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub username: Option<Cow<'s, str>>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PushCommit<'s> {
+    pub id: &'s str,
+    #[serde(borrow = "'s")]
+    pub message: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub author: CommitAuthor<'s>,
+    #[serde(default, borrow = "'s")]
+    pub added: Vec<Cow<'s, str>>,
+    #[serde(default, borrow = "'s")]
+    pub removed: Vec<Cow<'s, str>>,
+    #[serde(default, borrow = "'s")]
+    pub modified: Vec<Cow<'s, str>>,
+}
+impl PushCommit<'_> {
+    pub fn message_headline(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+pub enum GitRef<'s> {
+    Branch(&'s str),
+    Tag(&'s str),
+    Other(&'s str),
+}
+impl<'s> GitRef<'s> {
+    pub fn parse(r: &'s str) -> Self {
+        if let Some(b) = r.strip_prefix("refs/heads/") {
+            Self::Branch(b)
+        } else if let Some(t) = r.strip_prefix("refs/tags/") {
+            Self::Tag(t)
+        } else {
+            Self::Other(r)
+        }
+    }
+
+    pub const fn name(&self) -> &'s str {
+        match self {
+            Self::Branch(n) | Self::Tag(n) | Self::Other(n) => n,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowConclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+    #[serde(other)]
+    Unknown,
+}
+impl WorkflowConclusion {
+    pub const fn is_failure(&self) -> bool {
+        matches!(self, Self::Failure | Self::TimedOut | Self::StartupFailure)
+    }
+
+    /// 成功とも失敗ともいえない(実行されなかった)
+    pub const fn is_inconclusive(&self) -> bool {
+        matches!(self, Self::Cancelled | Self::Skipped)
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct Actor {
+    pub login: String,
+    pub html_url: String,
+    pub avatar_url: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub name: String,
+    pub workflow_id: u64,
+    pub run_number: u64,
+    pub head_branch: String,
+    pub head_sha: String,
+    pub html_url: String,
+    pub conclusion: Option<WorkflowConclusion>,
+    pub run_started_at: Option<String>,
+    pub updated_at: String,
+    pub actor: Actor,
+    pub triggering_actor: Option<Actor>,
+}
+impl WorkflowRun {
+    pub fn duration(&self) -> Option<time::Duration> {
+        use time::format_description::well_known::Rfc3339;
+
+        let started = time::OffsetDateTime::parse(self.run_started_at.as_deref()?, &Rfc3339).ok()?;
+        let finished = time::OffsetDateTime::parse(&self.updated_at, &Rfc3339).ok()?;
+
+        Some(finished - started)
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRunJob {
+    pub name: String,
+    pub html_url: String,
+    pub conclusion: Option<WorkflowConclusion>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRunJobList {
+    pub jobs: Vec<WorkflowRunJob>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRunSummary {
+    pub run_number: u64,
+    pub conclusion: Option<WorkflowConclusion>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRunSummaryList {
+    pub workflow_runs: Vec<WorkflowRunSummary>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PendingDeploymentEnvironment {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UserRef {
+    pub login: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TeamRef {
+    pub slug: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", content = "reviewer")]
+pub enum PendingDeploymentReviewer {
+    User(UserRef),
+    Team(TeamRef),
+}
+
+#[derive(serde::Deserialize)]
+pub struct PendingDeployment {
+    pub environment: PendingDeploymentEnvironment,
+    pub reviewers: Vec<PendingDeploymentReviewer>,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentReviewState {
+    Approved,
+    Rejected,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Opened,
+    Closed,
+    Reopened,
+    Created,
+    ReadyForReview,
+    Waiting,
+    Published,
+    Prereleased,
+    Edited,
+    Labeled,
+    Unlabeled,
+    Submitted,
+    Dismissed,
+    Requested,
+    InProgress,
+    Completed,
+    /// 個別に扱っていないアクション(`assigned` など)
+    Other(String),
+}
+impl Action {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Opened => "opened",
+            Self::Closed => "closed",
+            Self::Reopened => "reopened",
+            Self::Created => "created",
+            Self::ReadyForReview => "ready_for_review",
+            Self::Waiting => "waiting",
+            Self::Published => "published",
+            Self::Prereleased => "prereleased",
+            Self::Edited => "edited",
+            Self::Labeled => "labeled",
+            Self::Unlabeled => "unlabeled",
+            Self::Submitted => "submitted",
+            Self::Dismissed => "dismissed",
+            Self::Requested => "requested",
+            Self::InProgress => "in_progress",
+            Self::Completed => "completed",
+            Self::Other(s) => s,
+        }
+    }
+
+    /// 通知は流さずに、issue/PRのスレッドの親だけを書き換えるアクション
+    pub fn is_silent_update(&self) -> bool {
+        matches!(self, Self::Labeled | Self::Unlabeled | Self::Edited)
+    }
+}
+impl From<&str> for Action {
+    fn from(s: &str) -> Self {
+        match s {
+            "opened" => Self::Opened,
+            "closed" => Self::Closed,
+            "reopened" => Self::Reopened,
+            "created" => Self::Created,
+            "ready_for_review" => Self::ReadyForReview,
+            "waiting" => Self::Waiting,
+            "published" => Self::Published,
+            "prereleased" => Self::Prereleased,
+            "edited" => Self::Edited,
+            "labeled" => Self::Labeled,
+            "unlabeled" => Self::Unlabeled,
+            "submitted" => Self::Submitted,
+            "dismissed" => Self::Dismissed,
+            "requested" => Self::Requested,
+            "in_progress" => Self::InProgress,
+            "completed" => Self::Completed,
+            _ => Self::Other(s.to_owned()),
+        }
+    }
+}
+impl<'de> serde::Deserialize<'de> for Action {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = Cow::<str>::deserialize(deserializer)?;
+        Ok(Self::from(&s as &str))
+    }
+}
+
+/// Webhookを新しく作ったか、既存のものを更新したか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookRegistration {
+    Created(u64),
+    Updated(u64),
+}
+
+/// GitHub Appのインストールトークンを取れなかった
+#[derive(Debug, thiserror::Error)]
+pub enum AppAuthError {
+    #[error("Failed to load github pkey: {0}")]
+    PrivateKey(#[source] jsonwebtoken::errors::Error),
+    #[error("Failed to encode jwt: {0}")]
+    Jwt(#[source] jsonwebtoken::errors::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+pub struct ApiClient<'s> {
+    token: String,
+    repo_fullname: &'s str,
+}
+impl<'s> ApiClient<'s> {
+    pub async fn new(
+        app_id_str: &str,
+        installation_id_str: &str,
+        private_key_pem: &str,
+        repo_fullname: &'s str,
+    ) -> Result<ApiClient<'s>, AppAuthError> {
+        #[derive(serde::Serialize)]
+        struct Payload<'s> {
+            iat: usize,
+            exp: usize,
+            iss: &'s str,
+        }
+        #[derive(serde::Serialize)]
+        struct BodyParameters<'s> {
+            repository: &'s str,
+        }
+        #[derive(serde::Deserialize)]
+        struct Response {
+            token: String,
+        }
+
+        let key =
+            jsonwebtoken::EncodingKey::from_rsa_pem(private_key_pem.as_bytes()).map_err(AppAuthError::PrivateKey)?;
+        let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+        let nowtime = time::OffsetDateTime::now_utc().unix_timestamp() as usize;
+        let payload = Payload {
+            iat: nowtime - 60,
+            exp: nowtime + 10 * 60,
+            iss: app_id_str,
+        };
+        let token = jsonwebtoken::encode(&header, &payload, &key).map_err(AppAuthError::Jwt)?;
+
+        let Response { token } = reqwest::Client::new()
+            .post(format!(
+                "https://api.github.com/app/installations/{installation_id_str}/access_tokens"
+            ))
+            .header(reqwest::header::AUTHORIZATION, format!("Bearer {token}"))
+            .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+            .json(&BodyParameters {
+                repository: repo_fullname,
+            })
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        Ok(Self { token, repo_fullname })
+    }
+
+    /// ユーザーアクセストークンで本人として呼び出すクライアント
+    pub fn with_user_token(token: String, repo_fullname: &'s str) -> Self {
+        Self { token, repo_fullname }
+    }
+
+    fn unauthorized_get_request(url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .get(url)
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+    }
+
+    fn authorized_get_request(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .get(url)
+            .header(reqwest::header::AUTHORIZATION, format!("token {}", self.token))
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+    }
+
+    fn authorized_post_request(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .post(url)
+            .header(reqwest::header::AUTHORIZATION, format!("bearer {}", self.token))
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+    }
+
+    fn authorized_patch_request(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .patch(url)
+            .header(reqwest::header::AUTHORIZATION, format!("bearer {}", self.token))
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+    }
+
+    fn authorized_delete_request(&self, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .delete(url)
+            .header(reqwest::header::AUTHORIZATION, format!("bearer {}", self.token))
+            .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+    }
+
+    pub async fn list_workflow_run_jobs(&self, run_id: u64) -> reqwest::Result<WorkflowRunJobList> {
+        let url = format!(
+            "https://api.github.com/repos/{}/actions/runs/{run_id}/jobs",
+            self.repo_fullname
+        );
+
+        self.authorized_get_request(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .query(&[("filter", "latest"), ("per_page", "100")])
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    /// 同じワークフロー・ブランチで指定した番号より前に完了した実行の結論を取得する
+    ///
+    /// キャンセルやスキップされた実行は飛ばして、成功か失敗かが決まっている実行まで遡る
+    pub async fn previous_workflow_run_conclusion(
+        &self,
+        workflow_id: u64,
+        branch: &str,
+        before_run_number: u64,
+    ) -> reqwest::Result<Option<WorkflowConclusion>> {
+        const PER_PAGE: usize = 100;
+        // 遡りすぎないように、ここまでに見つからなければ結論なしとする
+        const MAX_PAGES: usize = 5;
+
+        let url = format!(
+            "https://api.github.com/repos/{}/actions/workflows/{workflow_id}/runs",
+            self.repo_fullname
+        );
+
+        for page in 1..=MAX_PAGES {
+            let list: WorkflowRunSummaryList = self
+                .authorized_get_request(&url)
+                .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+                .query(&[("branch", branch), ("status", "completed")])
+                .query(&[("per_page", PER_PAGE), ("page", page)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let last_page = list.workflow_runs.len() < PER_PAGE;
+
+            // 新しい順に返ってくるので、最初に見つかったページに直前の実行がある
+            let previous = list
+                .workflow_runs
+                .into_iter()
+                .filter(|r| r.run_number < before_run_number)
+                .filter_map(|r| r.conclusion.map(|c| (r.run_number, c)))
+                .filter(|(_, c)| !c.is_inconclusive())
+                .max_by_key(|(n, _)| *n);
+            if let Some((_, c)) = previous {
+                return Ok(Some(c));
+            }
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub async fn pending_deployments(&self, run_id: u64) -> reqwest::Result<Vec<PendingDeployment>> {
+        let url = format!(
+            "https://api.github.com/repos/{}/actions/runs/{run_id}/pending_deployments",
+            self.repo_fullname
+        );
+
+        self.authorized_get_request(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    pub async fn review_pending_deployments(
+        &self,
+        run_id: u64,
+        environment_ids: &[u64],
+        state: DeploymentReviewState,
+        comment: &str,
+    ) -> reqwest::Result<()> {
+        #[derive(serde::Serialize)]
+        struct BodyParameters<'s> {
+            environment_ids: &'s [u64],
+            state: DeploymentReviewState,
+            comment: &'s str,
+        }
+
+        let url = format!(
+            "https://api.github.com/repos/{}/actions/runs/{run_id}/pending_deployments",
+            self.repo_fullname
+        );
+
+        self.authorized_post_request(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&BodyParameters {
+                environment_ids,
+                state,
+                comment,
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn is_team_member(&self, team_slug: &str, login: &str) -> reqwest::Result<bool> {
+        #[derive(serde::Deserialize)]
+        struct Membership {
+            state: String,
+        }
+
+        let org = self
+            .repo_fullname
+            .split_once('/')
+            .map_or(self.repo_fullname, |(o, _)| o);
+        let url = format!("https://api.github.com/orgs/{org}/teams/{team_slug}/memberships/{login}");
+
+        let resp = self
+            .authorized_get_request(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let Membership { state } = resp.error_for_status()?.json().await?;
+
+        Ok(state == "active")
+    }
+
+    /// ユーザーがこのリポジトリ(`owner/*` のクライアントではOrganization)の管理者か
+    pub async fn is_admin(&self, login: &str) -> reqwest::Result<bool> {
+        #[derive(serde::Deserialize)]
+        struct Membership {
+            state: String,
+            role: String,
+        }
+        #[derive(serde::Deserialize)]
+        struct Permission {
+            permission: String,
+        }
+
+        if let Some(org) = self.repo_fullname.strip_suffix("/*") {
+            let resp = self
+                .authorized_get_request(format!("https://api.github.com/orgs/{org}/memberships/{login}"))
+                .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+                .send()
+                .await?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(false);
+            }
+            let Membership { state, role } = resp.error_for_status()?.json().await?;
+
+            return Ok(state == "active" && role == "admin");
+        }
+
+        let url = format!(
+            "https://api.github.com/repos/{}/collaborators/{login}/permission",
+            self.repo_fullname
+        );
+        let resp = self
+            .authorized_get_request(url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let Permission { permission } = resp.error_for_status()?.json().await?;
+
+        Ok(permission == "admin")
+    }
+
+    /// 同じURLのWebhookがあれば設定を更新して、なければ作成する
+    ///
+    /// `owner/*` のクライアントではOrganizationのWebhookを対象にする
+    pub async fn register_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        events: &[&str],
+    ) -> reqwest::Result<WebhookRegistration> {
+        #[derive(serde::Deserialize)]
+        struct Hook {
+            id: u64,
+        }
+        #[derive(serde::Serialize)]
+        struct Config<'s> {
+            url: &'s str,
+            content_type: &'s str,
+            secret: &'s str,
+            insecure_ssl: &'s str,
+        }
+        #[derive(serde::Serialize)]
+        struct BodyParameters<'s> {
+            name: &'s str,
+            active: bool,
+            events: &'s [&'s str],
+            config: Config<'s>,
+        }
+
+        let body = BodyParameters {
+            name: "web",
+            active: true,
+            events,
+            config: Config {
+                url,
+                content_type: "json",
+                secret,
+                insecure_ssl: "0",
+            },
+        };
+
+        if let Some(id) = self.find_webhook(url).await? {
+            self.authorized_patch_request(format!("{}/{id}", self.hooks_url()))
+                .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+                .json(&body)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            return Ok(WebhookRegistration::Updated(id));
+        }
+
+        let created: Hook = self
+            .authorized_post_request(self.hooks_url())
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(WebhookRegistration::Created(created.id))
+    }
+
+    /// 同じURLのWebhookの購読イベントだけを変える(シークレットはそのまま)
+    ///
+    /// Webhookが見つからなければNone
+    pub async fn update_webhook_events(&self, url: &str, events: &[&str]) -> reqwest::Result<Option<u64>> {
+        #[derive(serde::Serialize)]
+        struct BodyParameters<'s> {
+            events: &'s [&'s str],
+        }
+
+        let Some(id) = self.find_webhook(url).await? else {
+            return Ok(None);
+        };
+        self.authorized_patch_request(format!("{}/{id}", self.hooks_url()))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&BodyParameters { events })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(Some(id))
+    }
+
+    /// Webhookを削除する
+    ///
+    /// もう削除されていたらfalse
+    pub async fn delete_webhook(&self, id: u64) -> reqwest::Result<bool> {
+        let resp = self
+            .authorized_delete_request(format!("{}/{id}", self.hooks_url()))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        resp.error_for_status()?;
+
+        Ok(true)
+    }
+
+    fn hooks_url(&self) -> String {
+        match self.repo_fullname.strip_suffix("/*") {
+            Some(org) => format!("https://api.github.com/orgs/{org}/hooks"),
+            None => format!("https://api.github.com/repos/{}/hooks", self.repo_fullname),
+        }
+    }
+
+    /// 指定したURLに送るWebhookのID
+    async fn find_webhook(&self, url: &str) -> reqwest::Result<Option<u64>> {
+        #[derive(serde::Deserialize)]
+        struct HookConfig {
+            url: Option<String>,
+        }
+        #[derive(serde::Deserialize)]
+        struct Hook {
+            id: u64,
+            config: HookConfig,
+        }
+
+        let hooks: Vec<Hook> = self
+            .authorized_get_request(self.hooks_url())
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .query(&[("per_page", "100")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(hooks
+            .into_iter()
+            .find(|h| h.config.url.as_deref() == Some(url))
+            .map(|h| h.id))
+    }
+
+    /// PRで変更されたファイルのパスをすべて取得する(APIの上限は3000件)
+    pub async fn list_pull_request_files(&self, number: usize) -> reqwest::Result<Vec<String>> {
+        #[derive(serde::Deserialize)]
+        struct PullRequestFile {
+            filename: String,
+            previous_filename: Option<String>,
+        }
+        const PER_PAGE: usize = 100;
+
+        let url = format!(
+            "https://api.github.com/repos/{}/pulls/{number}/files",
+            self.repo_fullname
+        );
+
+        let mut paths = Vec::new();
+        for page in 1.. {
+            let files: Vec<PullRequestFile> = self
+                .authorized_get_request(&url)
+                .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+                .query(&[("per_page", PER_PAGE), ("page", page)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let last_page = files.len() < PER_PAGE;
+            for f in files {
+                // リネームは移動元も変更されたものとして扱う
+                paths.extend(f.previous_filename);
+                paths.push(f.filename);
+            }
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(paths)
+    }
+
+    pub async fn query_pullrequest_flags(&self, number: usize) -> reqwest::Result<PullRequestFlags> {
+        let url = format!("https://api.github.com/repos/{}/pulls/{number}", self.repo_fullname);
+
+        self.authorized_get_request(url)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/vnd.github.shadow-cat-preview+json",
+            )
+            .send()
+            .await?
+            .json()
+            .await
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureVerificationError {
+    #[error("Signature header is missing")]
+    MissingSignature,
+    #[error("Signature header is malformed")]
+    MalformedSignature,
+    #[error("Signature does not match any active secret")]
+    Mismatch,
+}
+
+/// Webhookについてくる署名ヘッダ
+pub struct WebhookSignature<'s> {
+    /// `X-Hub-Signature-256`
+    pub sha256: Option<&'s str>,
+    /// `X-Hub-Signature`(SHA-1、古い形式)
+    pub sha1: Option<&'s str>,
+}
+
+/// ペイロードの署名を検証する
+///
+/// ローテーション中は複数のシークレットが有効なので、どれか1つで検証できればよい。
+/// SHA-1の署名は `allow_sha1` のときだけ、SHA-256の署名がない場合に使う
+pub fn verify_request<'k>(
+    payload: &str,
+    signature: &WebhookSignature,
+    secrets: impl IntoIterator<Item = &'k str>,
+    allow_sha1: bool,
+) -> Result<(), SignatureVerificationError> {
+    let (algorithm, signature) = match (signature.sha256, signature.sha1) {
+        (Some(s), _) => (
+            ring::hmac::HMAC_SHA256,
+            s.strip_prefix("sha256=")
+                .ok_or(SignatureVerificationError::MalformedSignature)?,
+        ),
+        (None, Some(s)) if allow_sha1 => (
+            ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            s.strip_prefix("sha1=")
+                .ok_or(SignatureVerificationError::MalformedSignature)?,
+        ),
+        _ => return Err(SignatureVerificationError::MissingSignature),
+    };
+    let signature = decode_hex(signature).ok_or(SignatureVerificationError::MalformedSignature)?;
+
+    let verified = secrets.into_iter().any(|secret| {
+        let key = ring::hmac::Key::new(algorithm, secret.as_bytes());
+        ring::hmac::verify(&key, payload.as_bytes(), &signature).is_ok()
+    });
+    if !verified {
+        return Err(SignatureVerificationError::Mismatch);
+    }
+
+    Ok(())
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    s.as_bytes()
+        .chunks_exact(2)
+        .map(|cs| {
+            let h = (cs[0] as char).to_digit(16)?;
+            let l = (cs[1] as char).to_digit(16)?;
+
+            Some(((h << 4) | l) as u8)
+        })
+        .collect()
+}
+
+mod event;
+pub use self::event::*;
+pub mod graphql;
+pub mod oauth;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &str = r#"{"zen":"Keep it logically awesome."}"#;
+
+    fn sign(algorithm: ring::hmac::Algorithm, prefix: &str, secret: &str) -> String {
+        let key = ring::hmac::Key::new(algorithm, secret.as_bytes());
+        let tag = ring::hmac::sign(&key, PAYLOAD.as_bytes());
+
+        format!(
+            "{prefix}{}",
+            tag.as_ref().iter().map(|b| format!("{b:02x}")).collect::<String>()
+        )
+    }
+
+    fn sha256(secret: &str) -> String {
+        sign(ring::hmac::HMAC_SHA256, "sha256=", secret)
+    }
+
+    fn sha1(secret: &str) -> String {
+        sign(ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, "sha1=", secret)
+    }
+
+    fn verify(
+        sha256: Option<&str>,
+        sha1: Option<&str>,
+        secrets: &[&str],
+        allow_sha1: bool,
+    ) -> Result<(), SignatureVerificationError> {
+        verify_request(
+            PAYLOAD,
+            &WebhookSignature { sha256, sha1 },
+            secrets.iter().copied(),
+            allow_sha1,
+        )
+    }
+
+    #[test]
+    fn accepts_valid_sha256_signature() {
+        assert!(verify(Some(&sha256("secret")), None, &["secret"], false).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_signature() {
+        assert!(matches!(
+            verify(None, None, &["secret"], true),
+            Err(SignatureVerificationError::MissingSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_signature_without_prefix() {
+        let signature = sha256("secret");
+        let signature = signature.trim_start_matches("sha256=");
+
+        assert!(matches!(
+            verify(Some(signature), None, &["secret"], false),
+            Err(SignatureVerificationError::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_odd_length_signature() {
+        let mut signature = sha256("secret");
+        signature.pop();
+
+        assert!(matches!(
+            verify(Some(&signature), None, &["secret"], false),
+            Err(SignatureVerificationError::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_non_hex_signature() {
+        let signature = format!("sha256={}", "zz".repeat(32));
+
+        assert!(matches!(
+            verify(Some(&signature), None, &["secret"], false),
+            Err(SignatureVerificationError::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_non_ascii_signature() {
+        assert!(matches!(
+            verify(Some("sha256=あい"), None, &["secret"], false),
+            Err(SignatureVerificationError::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_signature_from_unknown_secret() {
+        assert!(matches!(
+            verify(Some(&sha256("other")), None, &["secret"], false),
+            Err(SignatureVerificationError::Mismatch)
+        ));
+    }
+
+    #[test]
+    fn accepts_signature_from_rotated_secret() {
+        assert!(verify(Some(&sha256("previous")), None, &["current", "previous"], false).is_ok());
+    }
+
+    #[test]
+    fn rejects_sha1_unless_allowed() {
+        let signature = sha1("secret");
+
+        assert!(matches!(
+            verify(None, Some(&signature), &["secret"], false),
+            Err(SignatureVerificationError::MissingSignature)
+        ));
+        assert!(verify(None, Some(&signature), &["secret"], true).is_ok());
+    }
+
+    #[test]
+    fn prefers_sha256_over_sha1() {
+        // SHA-256の署名があれば、正しいSHA-1の署名がついていてもそちらは見ない
+        assert!(matches!(
+            verify(Some(&sha256("other")), Some(&sha1("secret")), &["secret"], true),
+            Err(SignatureVerificationError::Mismatch)
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+//! `X-GitHub-Event` ごとのWebhookペイロード
+
+use std::borrow::Cow;
+
+use super::{
+    Action, Comment, DeploymentInfo, Discussion, GitRef, Issue, PullRequest, PushCommit, Release, Repository, Review,
+    User, WorkflowJob, WorkflowRun,
+};
+use crate::{EventKind, RoutingSubject};
+
+#[derive(serde::Deserialize)]
+pub struct IssuesEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub issue: Issue<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct IssueCommentEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub issue: Issue<'s>,
+    #[serde(borrow = "'s")]
+    pub comment: Comment<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PullRequestEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub pull_request: PullRequest<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PullRequestReviewEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub pull_request: PullRequest<'s>,
+    #[serde(borrow = "'s")]
+    pub review: Review<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PullRequestReviewCommentEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub pull_request: PullRequest<'s>,
+    #[serde(borrow = "'s")]
+    pub comment: Comment<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DiscussionEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub discussion: Discussion<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DiscussionCommentEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub discussion: Discussion<'s>,
+    #[serde(borrow = "'s")]
+    pub comment: Comment<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowJobEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub workflow_job: WorkflowJob<'s>,
+    #[serde(borrow = "'s")]
+    pub deployment: Option<DeploymentInfo<'s>>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorkflowRunEvent<'s> {
+    pub action: Action,
+    pub workflow_run: WorkflowRun,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReleaseEvent<'s> {
+    pub action: Action,
+    #[serde(borrow = "'s")]
+    pub release: Release<'s>,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PushEvent<'s> {
+    #[serde(rename = "ref", borrow = "'s")]
+    pub git_ref: Cow<'s, str>,
+    #[serde(borrow = "'s")]
+    pub commits: Vec<PushCommit<'s>>,
+    #[serde(borrow = "'s")]
+    pub compare: Option<Cow<'s, str>>,
+    #[serde(default)]
+    pub created: bool,
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub forced: bool,
+    #[serde(borrow = "'s")]
+    pub repository: Repository<'s>,
+    #[serde(borrow = "'s")]
+    pub sender: User<'s>,
+}
+
+/// Webhookを作ったときに届くping
+#[derive(serde::Deserialize)]
+pub struct PingEvent<'s> {
+    pub hook_id: u64,
+    #[serde(borrow = "'s")]
+    pub hook: Hook<'s>,
+    /// OrganizationのWebhookでは含まれない
+    #[serde(borrow = "'s")]
+    pub repository: Option<Repository<'s>>,
+    #[serde(borrow = "'s")]
+    pub organization: Option<Organization<'s>>,
+    #[serde(borrow = "'s")]
+    pub sender: Option<User<'s>>,
+}
+#[derive(serde::Deserialize)]
+pub struct Hook<'s> {
+    pub active: bool,
+    /// 購読しているイベント名(`*` ならすべて)
+    #[serde(borrow = "'s")]
+    pub events: Vec<Cow<'s, str>>,
+    #[serde(borrow = "'s")]
+    pub config: HookConfig<'s>,
+}
+#[derive(serde::Deserialize)]
+pub struct HookConfig<'s> {
+    /// `json` もしくは `form`
+    #[serde(borrow = "'s")]
+    pub content_type: Option<Cow<'s, str>>,
+}
+#[derive(serde::Deserialize)]
+pub struct Organization<'s> {
+    #[serde(borrow = "'s")]
+    pub login: Cow<'s, str>,
+}
+impl PingEvent<'_> {
+    /// Webhookを設定した対象(リポジトリ名、OrganizationのWebhookなら `owner/*`)
+    pub fn hook_target(&self) -> Option<Cow<'_, str>> {
+        match (&self.repository, &self.organization) {
+            (Some(r), _) => Some(Cow::Borrowed(&r.full_name)),
+            (None, Some(o)) => Some(Cow::Owned(format!("{}/*", o.login))),
+            (None, None) => None,
+        }
+    }
+
+    /// 購読しているイベントにこの種類が含まれているか
+    pub fn subscribes(&self, kind: EventKind) -> bool {
+        self.hook.events.iter().any(|e| e == "*" || e == kind.as_str())
+    }
+}
+
+/// `X-GitHub-Event` ヘッダで選んだWebhookのペイロード
+pub enum WebhookPayload<'s> {
+    Issues(IssuesEvent<'s>),
+    IssueComment(IssueCommentEvent<'s>),
+    PullRequest(PullRequestEvent<'s>),
+    PullRequestReview(PullRequestReviewEvent<'s>),
+    PullRequestReviewComment(PullRequestReviewCommentEvent<'s>),
+    Discussion(DiscussionEvent<'s>),
+    DiscussionComment(DiscussionCommentEvent<'s>),
+    WorkflowJob(WorkflowJobEvent<'s>),
+    WorkflowRun(WorkflowRunEvent<'s>),
+    Release(ReleaseEvent<'s>),
+    Push(PushEvent<'s>),
+    Ping(PingEvent<'s>),
+    /// 扱っていないイベント(イベント名)
+    Unknown(Cow<'s, str>),
+}
+impl<'s> WebhookPayload<'s> {
+    pub fn parse(event_name: &'s str, body: &'s str) -> serde_json::Result<Self> {
+        if event_name == "ping" {
+            return serde_json::from_str(body).map(Self::Ping);
+        }
+        let Ok(kind) = event_name.parse() else {
+            return Ok(Self::Unknown(Cow::Borrowed(event_name)));
+        };
+
+        Ok(match kind {
+            EventKind::Issues => Self::Issues(serde_json::from_str(body)?),
+            EventKind::IssueComment => Self::IssueComment(serde_json::from_str(body)?),
+            EventKind::PullRequest => Self::PullRequest(serde_json::from_str(body)?),
+            EventKind::PullRequestReview => Self::PullRequestReview(serde_json::from_str(body)?),
+            EventKind::PullRequestReviewComment => Self::PullRequestReviewComment(serde_json::from_str(body)?),
+            EventKind::Discussion => Self::Discussion(serde_json::from_str(body)?),
+            EventKind::DiscussionComment => Self::DiscussionComment(serde_json::from_str(body)?),
+            EventKind::WorkflowJob => Self::WorkflowJob(serde_json::from_str(body)?),
+            EventKind::WorkflowRun => Self::WorkflowRun(serde_json::from_str(body)?),
+            EventKind::Release => Self::Release(serde_json::from_str(body)?),
+            EventKind::Push => Self::Push(serde_json::from_str(body)?),
+        })
+    }
+
+    pub const fn kind(&self) -> Option<EventKind> {
+        Some(match self {
+            Self::Issues(_) => EventKind::Issues,
+            Self::IssueComment(_) => EventKind::IssueComment,
+            Self::PullRequest(_) => EventKind::PullRequest,
+            Self::PullRequestReview(_) => EventKind::PullRequestReview,
+            Self::PullRequestReviewComment(_) => EventKind::PullRequestReviewComment,
+            Self::Discussion(_) => EventKind::Discussion,
+            Self::DiscussionComment(_) => EventKind::DiscussionComment,
+            Self::WorkflowJob(_) => EventKind::WorkflowJob,
+            Self::WorkflowRun(_) => EventKind::WorkflowRun,
+            Self::Release(_) => EventKind::Release,
+            Self::Push(_) => EventKind::Push,
+            Self::Ping(_) | Self::Unknown(_) => return None,
+        })
+    }
+
+    pub const fn action(&self) -> Option<&Action> {
+        Some(match self {
+            Self::Issues(e) => &e.action,
+            Self::IssueComment(e) => &e.action,
+            Self::PullRequest(e) => &e.action,
+            Self::PullRequestReview(e) => &e.action,
+            Self::PullRequestReviewComment(e) => &e.action,
+            Self::Discussion(e) => &e.action,
+            Self::DiscussionComment(e) => &e.action,
+            Self::WorkflowJob(e) => &e.action,
+            Self::WorkflowRun(e) => &e.action,
+            Self::Release(e) => &e.action,
+            Self::Push(_) | Self::Ping(_) | Self::Unknown(_) => return None,
+        })
+    }
+
+    pub const fn repository(&self) -> Option<&Repository<'s>> {
+        Some(match self {
+            Self::Issues(e) => &e.repository,
+            Self::IssueComment(e) => &e.repository,
+            Self::PullRequest(e) => &e.repository,
+            Self::PullRequestReview(e) => &e.repository,
+            Self::PullRequestReviewComment(e) => &e.repository,
+            Self::Discussion(e) => &e.repository,
+            Self::DiscussionComment(e) => &e.repository,
+            Self::WorkflowJob(e) => &e.repository,
+            Self::WorkflowRun(e) => &e.repository,
+            Self::Release(e) => &e.repository,
+            Self::Push(e) => &e.repository,
+            Self::Ping(e) => return e.repository.as_ref(),
+            Self::Unknown(_) => return None,
+        })
+    }
+
+    pub const fn sender(&self) -> Option<&User<'s>> {
+        Some(match self {
+            Self::Issues(e) => &e.sender,
+            Self::IssueComment(e) => &e.sender,
+            Self::PullRequest(e) => &e.sender,
+            Self::PullRequestReview(e) => &e.sender,
+            Self::PullRequestReviewComment(e) => &e.sender,
+            Self::Discussion(e) => &e.sender,
+            Self::DiscussionComment(e) => &e.sender,
+            Self::WorkflowJob(e) => &e.sender,
+            Self::WorkflowRun(e) => &e.sender,
+            Self::Release(e) => &e.sender,
+            Self::Push(e) => &e.sender,
+            Self::Ping(e) => return e.sender.as_ref(),
+            Self::Unknown(_) => return None,
+        })
+    }
+
+    /// PRに関するイベントならその番号
+    pub fn pull_request_number(&self) -> Option<usize> {
+        match self {
+            Self::PullRequest(PullRequestEvent { pull_request, .. })
+            | Self::PullRequestReview(PullRequestReviewEvent { pull_request, .. })
+            | Self::PullRequestReviewComment(PullRequestReviewCommentEvent { pull_request, .. }) => {
+                Some(pull_request.number)
+            }
+            Self::Issues(IssuesEvent { issue, .. }) | Self::IssueComment(IssueCommentEvent { issue, .. })
+                if issue.is_pr() =>
+            {
+                Some(issue.number)
+            }
+            _ => None,
+        }
+    }
+
+    /// ルーティングルールの判定に使う情報を集める
+    ///
+    /// PRの変更ファイルはペイロードに含まれないので、必要なら別途APIで取得して埋める
+    pub fn routing_subject(&self) -> RoutingSubject<'_> {
+        let mut subject = RoutingSubject {
+            author: self.sender().map(|s| &s.login as &str),
+            ..Default::default()
+        };
+
+        match self {
+            Self::Issues(IssuesEvent { issue, .. }) | Self::IssueComment(IssueCommentEvent { issue, .. }) => {
+                subject.labels = issue.labels.iter().map(|l| l.name).collect();
+                subject.author = Some(&issue.user.login);
+            }
+            Self::PullRequest(PullRequestEvent { pull_request: pr, .. })
+            | Self::PullRequestReview(PullRequestReviewEvent { pull_request: pr, .. })
+            | Self::PullRequestReviewComment(PullRequestReviewCommentEvent { pull_request: pr, .. }) => {
+                subject.labels = pr.labels.iter().map(|l| l.name).collect();
+                subject.base_branch = Some(&pr.base.git_ref);
+                subject.head_branch = Some(&pr.head.git_ref);
+                subject.author = Some(&pr.user.login);
+            }
+            Self::Discussion(DiscussionEvent { discussion: d, .. })
+            | Self::DiscussionComment(DiscussionCommentEvent { discussion: d, .. }) => {
+                subject.author = Some(&d.user.login);
+            }
+            Self::WorkflowRun(e) => subject.head_branch = Some(&e.workflow_run.head_branch),
+            Self::WorkflowJob(e) => subject.head_branch = Some(e.workflow_job.head_branch),
+            Self::Push(e) => {
+                if let GitRef::Branch(b) = GitRef::parse(&e.git_ref) {
+                    subject.base_branch = Some(b);
+                }
+                subject.changed_paths = e
+                    .commits
+                    .iter()
+                    .flat_map(|c| c.added.iter().chain(&c.removed).chain(&c.modified))
+                    .map(|p| Cow::Borrowed(&p[..]))
+                    .collect();
+            }
+            Self::Release(_) | Self::Ping(_) | Self::Unknown(_) => (),
+        }
+
+        subject
+    }
+}
@@ -0,0 +1,245 @@
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub end_cursor: Option<String>,
+    pub has_next_page: Option<bool>,
+}
+impl PageInfo {
+    /// 次のページがあればそのカーソル
+    pub fn next_cursor(self) -> Option<String> {
+        if self.has_next_page == Some(true) {
+            self.end_cursor
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TeamMember {
+    pub login: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "__typename")]
+pub enum DeploymentReviewer {
+    User {
+        login: String,
+    },
+    Team {
+        name: String,
+        #[serde(rename = "combinedSlug")]
+        combined_slug: String,
+        members: Connection<TeamMember>,
+    },
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connection<Node> {
+    pub nodes: Vec<Node>,
+    pub page_info: Option<PageInfo>,
+}
+
+pub type DeploymentReviewerConnection = Connection<DeploymentReviewer>;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentProtectionRule {
+    pub reviewers: DeploymentReviewerConnection,
+}
+
+pub type DeploymentProtectionRuleConnection = Connection<DeploymentProtectionRule>;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Environment {
+    pub protection_rules: DeploymentProtectionRuleConnection,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Repository {
+    pub environment: Environment,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RepositoryCapture {
+    pub repository: Repository,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitActor {
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    pub message: String,
+    pub committer: GitActor,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(transparent)]
+pub struct QueryError(pub serde_json::Value);
+impl std::error::Error for QueryError {}
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as std::fmt::Debug>::fmt(self, f)
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryResponse<ResponseData> {
+    Data(ResponseData),
+    Errors(serde_json::Value),
+}
+impl<D> QueryResponse<D> {
+    pub fn data(self) -> Result<D, QueryError> {
+        match self {
+            Self::Data(d) => Ok(d),
+            Self::Errors(e) => Err(QueryError(e)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GraphQLError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    Query(#[from] QueryError),
+}
+
+#[derive(serde::Serialize)]
+pub struct GraphQLPostForm<'s> {
+    pub query: &'s str,
+}
+
+const REVIEWERS_PAGE_SIZE: usize = 50;
+const TEAM_MEMBERS_PAGE_SIZE: usize = 100;
+
+impl super::ApiClient<'_> {
+    pub fn commit_message_and_committer_name_query(&self, sha: &str) -> String {
+        let url = format!("https://github.com/{}/commit/{sha}", self.repo_fullname);
+
+        format!("resource(url: {url:?}) {{ ...on Commit {{ message committer {{ name }} }} }}")
+    }
+
+    /// 保護ルールを1件ずつ、そのレビュアーを`REVIEWERS_PAGE_SIZE`件ずつ取得するクエリ
+    pub fn environment_protection_rule_query(
+        &self,
+        environment_name: &str,
+        rules_after: Option<&str>,
+        reviewers_after: Option<&str>,
+    ) -> String {
+        let (repo_owner, repo_name) = self.repo_fullname.split_once('/').unwrap_or((self.repo_fullname, ""));
+        let after = |c: Option<&str>| c.map_or(String::new(), |c| format!(", after: {c:?}"));
+
+        format!(
+            r#"repository(owner: {repo_owner:?}, name: {repo_name:?}) {{
+            environment(name: {environment_name:?}) {{
+                protectionRules(first: 1{rules_after}) {{
+                    pageInfo {{ endCursor hasNextPage }}
+                    nodes {{
+                        reviewers(first: {REVIEWERS_PAGE_SIZE}{reviewers_after}) {{
+                            pageInfo {{ endCursor hasNextPage }}
+                            nodes {{
+                                __typename
+                                ... on User {{ login }}
+                                ... on Team {{
+                                    name
+                                    combinedSlug
+                                    members(first: {TEAM_MEMBERS_PAGE_SIZE}) {{
+                                        nodes {{ login }}
+                                    }}
+                                }}
+                            }}
+                        }}
+                    }}
+                }}
+            }}
+        }}"#,
+            rules_after = after(rules_after),
+            reviewers_after = after(reviewers_after),
+        )
+    }
+
+    async fn query_environment_protection_rules(
+        &self,
+        environment_name: &str,
+        rules_after: Option<&str>,
+        reviewers_after: Option<&str>,
+    ) -> Result<DeploymentProtectionRuleConnection, GraphQLError> {
+        let query = format!(
+            "query {{ {} }}",
+            self.environment_protection_rule_query(environment_name, rules_after, reviewers_after)
+        );
+        let captured = self
+            .post_graphql::<QueryResponse<RepositoryCapture>>(&query)
+            .await?
+            .data()?;
+
+        Ok(captured.repository.environment.protection_rules)
+    }
+
+    /// `environment_protection_rule_query`で取れた最初のページから、残りのページを辿ってレビュアーをすべて集める
+    pub async fn collect_environment_reviewers(
+        &self,
+        environment_name: &str,
+        first_page: Repository,
+    ) -> Result<Vec<DeploymentReviewer>, GraphQLError> {
+        let mut reviewers = Vec::new();
+        let mut rules = first_page.environment.protection_rules;
+        // 今見ている保護ルールの直前を指すカーソル
+        let mut rules_after = None::<String>;
+
+        loop {
+            let Connection { nodes, page_info, .. } = rules;
+            let Some(rule) = nodes.into_iter().next() else {
+                break;
+            };
+
+            let mut rule_reviewers = rule.reviewers;
+            loop {
+                reviewers.extend(rule_reviewers.nodes);
+                let Some(cursor) = rule_reviewers.page_info.and_then(PageInfo::next_cursor) else {
+                    break;
+                };
+
+                let next = self
+                    .query_environment_protection_rules(environment_name, rules_after.as_deref(), Some(&cursor))
+                    .await?;
+                let Some(r) = next.nodes.into_iter().next() else {
+                    break;
+                };
+                rule_reviewers = r.reviewers;
+            }
+
+            let Some(cursor) = page_info.and_then(PageInfo::next_cursor) else {
+                break;
+            };
+            rules = self
+                .query_environment_protection_rules(environment_name, Some(&cursor), None)
+                .await?;
+            rules_after = Some(cursor);
+        }
+
+        Ok(reviewers)
+    }
+
+    pub async fn post_graphql<R: serde::de::DeserializeOwned>(&self, query: &str) -> reqwest::Result<R> {
+        let s = self
+            .authorized_post_request("https://api.github.com/graphql")
+            .json(&GraphQLPostForm { query })
+            .send()
+            .await?
+            .text()
+            .await?;
+        Ok(serde_json::from_str(&s)
+            .unwrap_or_else(|e| panic!("Failed to decode object while parsing response: {s}: {e:?}")))
+    }
+}
@@ -0,0 +1,100 @@
+//! GitHub Appのユーザー認可(user-to-server)
+//!
+//! Slackのユーザーが本当にそのGitHubアカウントの持ち主であることを確かめて、
+//! 本人の権限でAPIを呼ぶために使う
+
+#[derive(Debug, thiserror::Error)]
+pub enum OAuthError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("GitHub rejected the token request: {0}")]
+    Rejected(String),
+}
+
+/// ユーザーアクセストークン(期限付きトークンが有効なAppならリフレッシュトークンも付く)
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct UserAccessToken {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// アクセストークンの有効期間(秒)
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+/// 認可画面のURL
+pub fn authorize_url(client_id: &str, state: &str) -> String {
+    format!("https://github.com/login/oauth/authorize?client_id={client_id}&state={state}")
+}
+
+/// 認可画面から戻ってきたcodeをアクセストークンに引き換える
+pub async fn exchange_code(client_id: &str, client_secret: &str, code: &str) -> Result<UserAccessToken, OAuthError> {
+    request_token(&[
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+        ("code", code),
+    ])
+    .await
+}
+
+/// 期限切れのアクセストークンを取り直す
+pub async fn refresh_token(
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str,
+) -> Result<UserAccessToken, OAuthError> {
+    request_token(&[
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+    ])
+    .await
+}
+
+async fn request_token(params: &[(&str, &str)]) -> Result<UserAccessToken, OAuthError> {
+    // 失敗しても200でerrorフィールドが返ってくる
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Response {
+        Token(UserAccessToken),
+        Error { error: String },
+    }
+
+    let resp = reqwest::Client::new()
+        .post("https://github.com/login/oauth/access_token")
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+        .form(params)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    match resp {
+        Response::Token(t) => Ok(t),
+        Response::Error { error } => Err(OAuthError::Rejected(error)),
+    }
+}
+
+/// アクセストークンの持ち主のログイン名
+pub async fn authenticated_user_login(access_token: &str) -> reqwest::Result<String> {
+    #[derive(serde::Deserialize)]
+    struct AuthenticatedUser {
+        login: String,
+    }
+
+    let AuthenticatedUser { login } = reqwest::Client::new()
+        .get("https://api.github.com/user")
+        .header(reqwest::header::AUTHORIZATION, format!("bearer {access_token}"))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .header(reqwest::header::USER_AGENT, "koyuki/repoact-notify")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(login)
+}
@@ -0,0 +1,40 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::RouteReadWriteError;
+
+/// 通知せずに読み捨てたイベント/アクションの組み合わせの集計
+///
+/// 対応するアクションを増やすときの目安にする
+pub struct IgnoredEventCounter;
+impl IgnoredEventCounter {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-IgnoredEvents";
+
+    /// `event:action`(アクションがなければイベント名だけ)のカウントを1増やす
+    pub async fn increment(
+        client: &aws_sdk_dynamodb::Client,
+        event_name: &str,
+        action: Option<&str>,
+    ) -> Result<(), RouteReadWriteError> {
+        let key = match action {
+            Some(a) => format!("{event_name}:{a}"),
+            None => event_name.to_owned(),
+        };
+
+        client
+            .update_item()
+            .table_name(Self::TABLE_NAME)
+            .key("event_action", AttributeValue::S(key))
+            .update_expression("ADD #count :one SET last_seen_at = :now")
+            .expression_attribute_names("#count", "count")
+            .expression_attribute_values(":one", AttributeValue::N("1".into()))
+            .expression_attribute_values(
+                ":now",
+                AttributeValue::N(time::OffsetDateTime::now_utc().unix_timestamp().to_string()),
+            )
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+}
@@ -1,3 +1,19 @@
+mod audit;
+pub use self::audit::*;
+mod delivery;
+pub use self::delivery::*;
+pub mod deployment_review;
+mod event_filter;
+pub use self::event_filter::*;
+pub mod github;
+mod ignored_event;
+pub use self::ignored_event::*;
 mod route;
 pub use self::route::*;
+mod routing_rule;
+pub use self::routing_rule::*;
 pub mod slack;
+mod thread;
+pub use self::thread::*;
+mod user_map;
+pub use self::user_map::*;
@@ -1,24 +1,135 @@
 use aws_sdk_dynamodb::types::AttributeValue;
 
+use std::collections::HashMap;
+
+use crate::{glob_match, EventFilter, EventKind, RoutingRule};
+
 #[derive(Debug, thiserror::Error)]
 pub enum RouteReadWriteError {
     #[error("Route record key {0} is not found in the record")]
     KeyNotFound(&'static str),
     #[error("Route record key {0} is not a string")]
     ValueIsNotString(&'static str),
+    #[error("Route record key {0} is not a bool")]
+    ValueIsNotBool(&'static str),
+    #[error("Route record key {0} is not a number")]
+    ValueIsNotNumber(&'static str),
+    #[error("Route record key {0} is not a string set")]
+    ValueIsNotStringSet(&'static str),
+    #[error("Route record key {0} is not a list")]
+    ValueIsNotList(&'static str),
+    #[error("Route record key {0} is not a map")]
+    ValueIsNotMap(&'static str),
+    #[error("Route record key {0} has an invalid value: {1:?}")]
+    InvalidValue(&'static str, String),
     #[error(transparent)]
-    DynamoDBError(#[from] aws_sdk_dynamodb::Error),
+    DynamoDBError(Box<aws_sdk_dynamodb::Error>),
+}
+impl From<aws_sdk_dynamodb::Error> for RouteReadWriteError {
+    fn from(e: aws_sdk_dynamodb::Error) -> Self {
+        Self::DynamoDBError(Box::new(e))
+    }
+}
+
+/// workflow_runの完了通知をどこまで流すか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkflowRunNotifyMode {
+    /// 完了したものはすべて通知する
+    #[default]
+    All,
+    /// 失敗と、失敗からの復旧(失敗後はじめての成功)だけ通知する
+    FailuresAndRecoveries,
+}
+impl WorkflowRunNotifyMode {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::FailuresAndRecoveries => "failures_and_recoveries",
+        }
+    }
+}
+impl std::str::FromStr for WorkflowRunNotifyMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "all" => Ok(Self::All),
+            "failures_and_recoveries" => Ok(Self::FailuresAndRecoveries),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 通知メッセージの組み立て方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageFormat {
+    /// 従来のattachments
+    #[default]
+    Attachments,
+    /// Block Kit
+    BlockKit,
+}
+impl MessageFormat {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Attachments => "attachments",
+            Self::BlockKit => "block_kit",
+        }
+    }
+}
+impl std::str::FromStr for MessageFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "attachments" => Ok(Self::Attachments),
+            "block_kit" => Ok(Self::BlockKit),
+            _ => Err(()),
+        }
+    }
 }
 
 pub struct Route {
     pub repository_fullpath: String,
     pub channel_id: String,
+    pub workflow_run_notify: WorkflowRunNotifyMode,
+    /// スレッドに返信する状態変化(close/reopen/merge)をチャンネルにも流すか
+    pub broadcast_state_changes: bool,
+    pub message_format: MessageFormat,
+    pub events: EventFilter,
+    /// 上から順に判定して、マッチしたルールのチャンネルすべてにも通知する
+    pub rules: Vec<RoutingRule>,
+    /// 個別にフィルタや表示形式を持つ追加の通知先
+    pub targets: Vec<ChannelTarget>,
+    /// `owner/*` のルートで、リポジトリごとにデフォルトのチャンネルを差し替える
+    pub repository_overrides: Vec<RepositoryOverride>,
+    /// `owner/*` のルートで受け付けるリポジトリ名のパターン(空ならすべて)
+    pub include_repositories: Vec<String>,
+    /// `owner/*` のルートで無視するリポジトリ名のパターン
+    pub exclude_repositories: Vec<String>,
+    /// ルート専用のWebhookシークレット(Secrets ManagerのARN)。なければ共通のシークレットを使う
+    pub secret_arn: Option<String>,
+    /// pingで接続を確認済みのWebhookのID
+    pub hook_id: Option<u64>,
+    /// `X-Hub-Signature-256` がないときに古いSHA-1の署名(`X-Hub-Signature`)でも受け付けるか
+    pub legacy_signature: bool,
+}
+
+/// リポジトリごとの通知先の差し替え
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryOverride {
+    /// リポジトリ名のパターン(オーナーは含まない)
+    pub repository: String,
+    pub channel_id: String,
 }
 impl Route {
     const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-RouteMap";
+    const CHANNEL_INDEX_NAME: &'static str = "channel_id-index";
+    /// ルート専用のWebhookシークレットをSecrets Managerに置くときの名前の接頭辞
+    pub const SECRET_NAME_PREFIX: &'static str = "repoact-notify/routes/";
 
     pub async fn get(client: &aws_sdk_dynamodb::Client, route_id: String) -> Result<Option<Self>, RouteReadWriteError> {
-        let Some(mut item) = client
+        let Some(item) = client
             .get_item()
             .table_name(Self::TABLE_NAME)
             .key("path", AttributeValue::S(route_id))
@@ -30,6 +141,88 @@ impl Route {
             return Ok(None);
         };
 
+        Self::decode(item).map(Some)
+    }
+
+    /// チャンネルに通知しているルートを(ルートのパスと組で)列挙する
+    ///
+    /// channel_idのGSIを引くので、ルート自身のチャンネルだけが対象(追加の通知先やルールのチャンネルは含まない)
+    pub async fn scan_by_channel(
+        client: &aws_sdk_dynamodb::Client,
+        channel_id: &str,
+    ) -> Result<Vec<(String, Self)>, RouteReadWriteError> {
+        let mut routes = Vec::new();
+        let mut exclusive_start_key = None;
+        loop {
+            let resp = client
+                .query()
+                .table_name(Self::TABLE_NAME)
+                .index_name(Self::CHANNEL_INDEX_NAME)
+                .key_condition_expression("channel_id = :channel_id")
+                .expression_attribute_values(":channel_id", AttributeValue::S(channel_id.into()))
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+            for item in resp.items.unwrap_or_default() {
+                routes.push(Self::decode_with_path(item)?);
+            }
+
+            exclusive_start_key = resp.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(routes)
+    }
+
+    /// すべてのルートを(ルートのパスと組で)列挙する
+    pub async fn scan_all(client: &aws_sdk_dynamodb::Client) -> Result<Vec<(String, Self)>, RouteReadWriteError> {
+        let mut routes = Vec::new();
+        let mut exclusive_start_key = None;
+        loop {
+            let resp = client
+                .scan()
+                .table_name(Self::TABLE_NAME)
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+            for item in resp.items.unwrap_or_default() {
+                routes.push(Self::decode_with_path(item)?);
+            }
+
+            exclusive_start_key = resp.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(routes)
+    }
+
+    /// ルートを削除する。存在しなかった場合はfalse
+    pub async fn delete(client: &aws_sdk_dynamodb::Client, route_id: String) -> Result<bool, RouteReadWriteError> {
+        let resp = client
+            .delete_item()
+            .table_name(Self::TABLE_NAME)
+            .key("path", AttributeValue::S(route_id))
+            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllOld)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(resp.attributes.is_some_and(|a| !a.is_empty()))
+    }
+
+    fn decode_with_path(mut item: HashMap<String, AttributeValue>) -> Result<(String, Self), RouteReadWriteError> {
+        let path = string_attr(&mut item, "path")?.ok_or(RouteReadWriteError::KeyNotFound("path"))?;
+
+        Ok((path, Self::decode(item)?))
+    }
+
+    fn decode(mut item: HashMap<String, AttributeValue>) -> Result<Self, RouteReadWriteError> {
         let repository_fullpath = match item.remove("repository_fullpath") {
             Some(AttributeValue::S(x)) => x,
             Some(_) => return Err(RouteReadWriteError::ValueIsNotString("repository_fullpath")),
@@ -41,23 +234,556 @@ impl Route {
             None => return Err(RouteReadWriteError::KeyNotFound("channel_id")),
         };
 
-        Ok(Some(Self {
+        let workflow_run_notify = match item.remove("workflow_run_notify") {
+            Some(AttributeValue::S(x)) => x
+                .parse()
+                .map_err(|_| RouteReadWriteError::InvalidValue("workflow_run_notify", x))?,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("workflow_run_notify")),
+            None => WorkflowRunNotifyMode::default(),
+        };
+
+        let broadcast_state_changes = bool_attr(&mut item, "broadcast_state_changes")?.unwrap_or(false);
+        let message_format = message_format_attr(&mut item)?;
+        let events = event_filter_attr(&mut item)?;
+
+        let rules = match item.remove("rules") {
+            Some(AttributeValue::L(xs)) => xs
+                .into_iter()
+                .map(|x| match x {
+                    AttributeValue::M(m) => Self::decode_rule(m),
+                    _ => Err(RouteReadWriteError::ValueIsNotMap("rules")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotList("rules")),
+            None => Vec::new(),
+        };
+
+        let targets = match item.remove("targets") {
+            Some(AttributeValue::L(xs)) => xs
+                .into_iter()
+                .map(|x| match x {
+                    AttributeValue::M(m) => ChannelTarget::decode(m),
+                    _ => Err(RouteReadWriteError::ValueIsNotMap("targets")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotList("targets")),
+            None => Vec::new(),
+        };
+
+        let repository_overrides = match item.remove("repository_overrides") {
+            Some(AttributeValue::L(xs)) => xs
+                .into_iter()
+                .map(|x| match x {
+                    AttributeValue::M(mut m) => Ok(RepositoryOverride {
+                        repository: string_attr(&mut m, "repository")?
+                            .ok_or(RouteReadWriteError::KeyNotFound("repository"))?,
+                        channel_id: string_attr(&mut m, "channel_id")?
+                            .ok_or(RouteReadWriteError::KeyNotFound("channel_id"))?,
+                    }),
+                    _ => Err(RouteReadWriteError::ValueIsNotMap("repository_overrides")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotList("repository_overrides")),
+            None => Vec::new(),
+        };
+        let include_repositories = string_list_attr(&mut item, "include_repositories")?;
+        let exclude_repositories = string_list_attr(&mut item, "exclude_repositories")?;
+        let secret_arn = string_attr(&mut item, "secret_arn")?;
+        let legacy_signature = bool_attr(&mut item, "legacy_signature")?.unwrap_or(false);
+        let hook_id = match item.remove("hook_id") {
+            Some(AttributeValue::N(x)) => Some(x.parse().map_err(|_| RouteReadWriteError::InvalidValue("hook_id", x))?),
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotNumber("hook_id")),
+            None => None,
+        };
+
+        Ok(Self {
             repository_fullpath,
             channel_id,
-        }))
+            workflow_run_notify,
+            broadcast_state_changes,
+            message_format,
+            events,
+            rules,
+            targets,
+            repository_overrides,
+            include_repositories,
+            exclude_repositories,
+            secret_arn,
+            hook_id,
+            legacy_signature,
+        })
     }
 
     pub async fn put(self, client: &aws_sdk_dynamodb::Client, route_id: String) -> Result<(), RouteReadWriteError> {
+        self.put_request(client, route_id)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// 同じパスのルートがまだなければ登録する。既にあった場合はfalse
+    pub async fn put_if_absent(
+        self,
+        client: &aws_sdk_dynamodb::Client,
+        route_id: String,
+    ) -> Result<bool, RouteReadWriteError> {
+        let r = self
+            .put_request(client, route_id)
+            .condition_expression("attribute_not_exists(#path)")
+            .expression_attribute_names("#path", "path")
+            .send()
+            .await;
+
+        match r {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(aws_sdk_dynamodb::Error::from(e).into()),
+        }
+    }
+
+    /// pingで接続を確認したWebhookのIDを記録する
+    pub async fn set_hook_id(
+        client: &aws_sdk_dynamodb::Client,
+        route_id: String,
+        hook_id: u64,
+    ) -> Result<(), RouteReadWriteError> {
         client
-            .put_item()
+            .update_item()
             .table_name(Self::TABLE_NAME)
-            .item("path", AttributeValue::S(route_id))
-            .item("repository_fullpath", AttributeValue::S(self.repository_fullpath))
-            .item("channel_id", AttributeValue::S(self.channel_id))
+            .key("path", AttributeValue::S(route_id))
+            .update_expression("SET hook_id = :hook_id")
+            .condition_expression("attribute_exists(#path)")
+            .expression_attribute_names("#path", "path")
+            .expression_attribute_values(":hook_id", AttributeValue::N(hook_id.to_string()))
             .send()
             .await
             .map_err(aws_sdk_dynamodb::Error::from)?;
 
         Ok(())
     }
+
+    fn put_request(
+        self,
+        client: &aws_sdk_dynamodb::Client,
+        route_id: String,
+    ) -> aws_sdk_dynamodb::operation::put_item::builders::PutItemFluentBuilder {
+        let mut req = client
+            .put_item()
+            .table_name(Self::TABLE_NAME)
+            .item("path", AttributeValue::S(route_id))
+            .item("repository_fullpath", AttributeValue::S(self.repository_fullpath))
+            .item("channel_id", AttributeValue::S(self.channel_id))
+            .item(
+                "workflow_run_notify",
+                AttributeValue::S(self.workflow_run_notify.as_str().into()),
+            )
+            .item(
+                "broadcast_state_changes",
+                AttributeValue::Bool(self.broadcast_state_changes),
+            )
+            .item("message_format", AttributeValue::S(self.message_format.as_str().into()));
+        if let Some(events) = encode_event_filter(&self.events) {
+            req = req.item("events", events);
+        }
+
+        if !self.rules.is_empty() {
+            req = req.item(
+                "rules",
+                AttributeValue::L(self.rules.into_iter().map(Self::encode_rule).collect()),
+            );
+        }
+
+        if !self.targets.is_empty() {
+            req = req.item(
+                "targets",
+                AttributeValue::L(self.targets.into_iter().map(ChannelTarget::encode).collect()),
+            );
+        }
+
+        if !self.repository_overrides.is_empty() {
+            req = req.item(
+                "repository_overrides",
+                AttributeValue::L(
+                    self.repository_overrides
+                        .into_iter()
+                        .map(|o| {
+                            AttributeValue::M(HashMap::from([
+                                (String::from("repository"), AttributeValue::S(o.repository)),
+                                (String::from("channel_id"), AttributeValue::S(o.channel_id)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        if !self.include_repositories.is_empty() {
+            req = req.item("include_repositories", encode_string_list(self.include_repositories));
+        }
+        if !self.exclude_repositories.is_empty() {
+            req = req.item("exclude_repositories", encode_string_list(self.exclude_repositories));
+        }
+        if let Some(arn) = self.secret_arn {
+            req = req.item("secret_arn", AttributeValue::S(arn));
+        }
+        if let Some(id) = self.hook_id {
+            req = req.item("hook_id", AttributeValue::N(id.to_string()));
+        }
+        if self.legacy_signature {
+            req = req.item("legacy_signature", AttributeValue::Bool(true));
+        }
+
+        req
+    }
+
+    fn decode_rule(mut m: HashMap<String, AttributeValue>) -> Result<RoutingRule, RouteReadWriteError> {
+        Ok(RoutingRule {
+            channel_id: string_attr(&mut m, "channel_id")?.ok_or(RouteReadWriteError::KeyNotFound("channel_id"))?,
+            labels: string_list_attr(&mut m, "labels")?,
+            base_branch: string_attr(&mut m, "base_branch")?,
+            head_branch: string_attr(&mut m, "head_branch")?,
+            paths: string_list_attr(&mut m, "paths")?,
+            author: string_attr(&mut m, "author")?,
+        })
+    }
+
+    fn encode_rule(rule: RoutingRule) -> AttributeValue {
+        let mut m = HashMap::new();
+        m.insert(String::from("channel_id"), AttributeValue::S(rule.channel_id));
+        m.insert(String::from("labels"), encode_string_list(rule.labels));
+        m.insert(String::from("paths"), encode_string_list(rule.paths));
+        if let Some(b) = rule.base_branch {
+            m.insert(String::from("base_branch"), AttributeValue::S(b));
+        }
+        if let Some(b) = rule.head_branch {
+            m.insert(String::from("head_branch"), AttributeValue::S(b));
+        }
+        if let Some(a) = rule.author {
+            m.insert(String::from("author"), AttributeValue::S(a));
+        }
+
+        AttributeValue::M(m)
+    }
+
+    /// Webhookを送ってきたリポジトリがこのルートのものか
+    ///
+    /// `owner/*` と登録されていればそのオーナーのリポジトリすべてを受け付ける(GitHubの名前は大文字小文字を区別しない)
+    pub fn accepts_repository(&self, full_name: &str) -> bool {
+        match self.repository_fullpath.strip_suffix("/*") {
+            Some(owner) => full_name
+                .split_once('/')
+                .is_some_and(|(o, _)| o.eq_ignore_ascii_case(owner)),
+            None => self.repository_fullpath.eq_ignore_ascii_case(full_name),
+        }
+    }
+
+    /// `owner/*` のルートでinclude/excludeの指定からこのリポジトリを受け付けるか(excludeが優先)
+    pub fn includes_repository(&self, full_name: &str) -> bool {
+        let name = full_name.split_once('/').map_or(full_name, |(_, n)| n);
+
+        (self.include_repositories.is_empty()
+            || self
+                .include_repositories
+                .iter()
+                .any(|p| repository_name_matches(p, name)))
+            && !self
+                .exclude_repositories
+                .iter()
+                .any(|p| repository_name_matches(p, name))
+    }
+
+    /// リポジトリに対するデフォルトのチャンネル(差し替えがなければルートのチャンネル)
+    ///
+    /// 複数の差し替えにマッチしたら先に登録されたものを使う
+    pub fn channel_for_repository(&self, full_name: &str) -> &str {
+        let name = full_name.split_once('/').map_or(full_name, |(_, n)| n);
+
+        self.repository_overrides
+            .iter()
+            .find(|o| repository_name_matches(&o.repository, name))
+            .map_or(&self.channel_id, |o| &o.channel_id)
+    }
+
+    /// Webhookで購読する必要があるイベント(どれかの通知先が受け取るもの)
+    pub fn subscribed_events(&self) -> Vec<EventKind> {
+        let mut kinds = self.events.kinds();
+        for t in &self.targets {
+            for k in t.events.kinds() {
+                if !kinds.contains(&k) {
+                    kinds.push(k);
+                }
+            }
+        }
+
+        kinds
+    }
+
+    /// ルート自身のチャンネルと追加の通知先をまとめたもの
+    pub fn all_targets(&self, repository_full_name: &str) -> Vec<ChannelTarget> {
+        let mut targets = vec![ChannelTarget {
+            channel_id: self.channel_for_repository(repository_full_name).into(),
+            events: self.events.clone(),
+            message_format: self.message_format,
+            broadcast_state_changes: self.broadcast_state_changes,
+        }];
+        targets.extend(self.targets.iter().cloned());
+
+        targets
+    }
+}
+
+/// 追加の通知先チャンネル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelTarget {
+    pub channel_id: String,
+    pub events: EventFilter,
+    pub message_format: MessageFormat,
+    /// スレッドに返信する状態変化(close/reopen/merge)をチャンネルにも流すか
+    pub broadcast_state_changes: bool,
+}
+impl ChannelTarget {
+    pub fn new(channel_id: String) -> Self {
+        Self {
+            channel_id,
+            events: EventFilter::default(),
+            message_format: MessageFormat::default(),
+            broadcast_state_changes: false,
+        }
+    }
+
+    fn decode(mut m: HashMap<String, AttributeValue>) -> Result<Self, RouteReadWriteError> {
+        Ok(Self {
+            channel_id: string_attr(&mut m, "channel_id")?.ok_or(RouteReadWriteError::KeyNotFound("channel_id"))?,
+            events: event_filter_attr(&mut m)?,
+            message_format: message_format_attr(&mut m)?,
+            broadcast_state_changes: bool_attr(&mut m, "broadcast_state_changes")?.unwrap_or(false),
+        })
+    }
+
+    fn encode(self) -> AttributeValue {
+        let mut m = HashMap::new();
+        m.insert(String::from("channel_id"), AttributeValue::S(self.channel_id));
+        m.insert(
+            String::from("message_format"),
+            AttributeValue::S(self.message_format.as_str().into()),
+        );
+        m.insert(
+            String::from("broadcast_state_changes"),
+            AttributeValue::Bool(self.broadcast_state_changes),
+        );
+        if let Some(events) = encode_event_filter(&self.events) {
+            m.insert(String::from("events"), events);
+        }
+
+        AttributeValue::M(m)
+    }
+}
+
+fn string_attr(
+    m: &mut HashMap<String, AttributeValue>,
+    key: &'static str,
+) -> Result<Option<String>, RouteReadWriteError> {
+    match m.remove(key) {
+        Some(AttributeValue::S(x)) => Ok(Some(x)),
+        Some(_) => Err(RouteReadWriteError::ValueIsNotString(key)),
+        None => Ok(None),
+    }
+}
+
+fn bool_attr(m: &mut HashMap<String, AttributeValue>, key: &'static str) -> Result<Option<bool>, RouteReadWriteError> {
+    match m.remove(key) {
+        Some(AttributeValue::Bool(x)) => Ok(Some(x)),
+        Some(_) => Err(RouteReadWriteError::ValueIsNotBool(key)),
+        None => Ok(None),
+    }
+}
+
+fn string_list_attr(
+    m: &mut HashMap<String, AttributeValue>,
+    key: &'static str,
+) -> Result<Vec<String>, RouteReadWriteError> {
+    match m.remove(key) {
+        Some(AttributeValue::L(xs)) => xs
+            .into_iter()
+            .map(|x| match x {
+                AttributeValue::S(x) => Ok(x),
+                _ => Err(RouteReadWriteError::ValueIsNotString(key)),
+            })
+            .collect(),
+        Some(_) => Err(RouteReadWriteError::ValueIsNotList(key)),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn encode_string_list(xs: Vec<String>) -> AttributeValue {
+    AttributeValue::L(xs.into_iter().map(AttributeValue::S).collect())
+}
+
+fn message_format_attr(m: &mut HashMap<String, AttributeValue>) -> Result<MessageFormat, RouteReadWriteError> {
+    match string_attr(m, "message_format")? {
+        Some(x) => x
+            .parse()
+            .map_err(|_| RouteReadWriteError::InvalidValue("message_format", x)),
+        None => Ok(MessageFormat::default()),
+    }
+}
+
+fn event_filter_attr(m: &mut HashMap<String, AttributeValue>) -> Result<EventFilter, RouteReadWriteError> {
+    let mut events = EventFilter::default();
+    match m.remove("events") {
+        Some(AttributeValue::Ss(xs)) => {
+            for x in xs {
+                match x.parse() {
+                    Ok(r) => events.rules.push(r),
+                    Err(_) => return Err(RouteReadWriteError::InvalidValue("events", x)),
+                }
+            }
+        }
+        Some(_) => return Err(RouteReadWriteError::ValueIsNotStringSet("events")),
+        None => (),
+    }
+
+    Ok(events)
+}
+
+/// リポジトリ名のパターンにマッチするか(GitHubの名前は大文字小文字を区別しない)
+fn repository_name_matches(pattern: &str, name: &str) -> bool {
+    glob_match(&pattern.to_ascii_lowercase(), &name.to_ascii_lowercase())
+}
+
+/// 空のString Setは保存できないので、フィルタなしの場合は属性ごと省く
+fn encode_event_filter(events: &EventFilter) -> Option<AttributeValue> {
+    (!events.is_empty()).then(|| AttributeValue::Ss(events.rules.iter().map(ToString::to_string).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(repository_fullpath: &str) -> Route {
+        Route {
+            repository_fullpath: repository_fullpath.into(),
+            channel_id: String::from("C_ROUTE"),
+            workflow_run_notify: WorkflowRunNotifyMode::default(),
+            broadcast_state_changes: false,
+            message_format: MessageFormat::default(),
+            events: EventFilter::default(),
+            rules: Vec::new(),
+            targets: Vec::new(),
+            repository_overrides: Vec::new(),
+            include_repositories: Vec::new(),
+            exclude_repositories: Vec::new(),
+            secret_arn: None,
+            hook_id: None,
+            legacy_signature: false,
+        }
+    }
+
+    #[test]
+    fn accepts_repository_table() {
+        let cases = [
+            // (route, repository, expected)
+            ("owner/repo", "owner/repo", true),
+            ("owner/repo", "Owner/Repo", true),
+            ("owner/repo", "owner/repo2", false),
+            ("owner/repo", "owner/rep", false),
+            ("owner/repo", "other/repo", false),
+            ("owner/*", "owner/repo", true),
+            ("owner/*", "OWNER/anything", true),
+            ("owner/*", "owner-evil/x", false),
+            ("owner/*", "evil-owner/x", false),
+            ("owner/*", "owner", false),
+            ("owner/*", "other/owner", false),
+        ];
+
+        for (r, repository, expected) in cases {
+            assert_eq!(
+                route(r).accepts_repository(repository),
+                expected,
+                "route={r:?} repository={repository:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn includes_repository_table() {
+        let cases: [(&[&str], &[&str], &str, bool); 12] = [
+            // (include, exclude, repository, expected)
+            (&[], &[], "owner/repo", true),
+            (&["repo"], &[], "owner/repo", true),
+            (&["repo"], &[], "owner/repo2", false),
+            (&["app-*"], &[], "owner/app-web", true),
+            (&["app-*"], &[], "owner/lib-web", false),
+            (&["app-*"], &[], "owner/App-Web", true),
+            (&["App-*"], &[], "owner/app-web", true),
+            (&[], &["*-archive"], "owner/app-archive", false),
+            (&[], &["*-archive"], "owner/app", true),
+            (&[], &["*-ARCHIVE"], "owner/app-archive", false),
+            // includeとexcludeの両方にマッチしたらexcludeが優先
+            (&["app-*"], &["app-legacy"], "owner/app-legacy", false),
+            (&["app-*"], &["app-legacy"], "owner/app-new", true),
+        ];
+
+        for (include, exclude, repository, expected) in cases {
+            let mut r = route("owner/*");
+            r.include_repositories = include.iter().map(|&p| p.into()).collect();
+            r.exclude_repositories = exclude.iter().map(|&p| p.into()).collect();
+
+            assert_eq!(
+                r.includes_repository(repository),
+                expected,
+                "include={include:?} exclude={exclude:?} repository={repository:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn channel_for_repository_table() {
+        let mut r = route("owner/*");
+        r.repository_overrides = vec![
+            RepositoryOverride {
+                repository: String::from("app-web"),
+                channel_id: String::from("C_WEB"),
+            },
+            RepositoryOverride {
+                repository: String::from("app-*"),
+                channel_id: String::from("C_APP"),
+            },
+            RepositoryOverride {
+                repository: String::from("*"),
+                channel_id: String::from("C_ANY"),
+            },
+        ];
+        let cases = [
+            // (repository, expected)
+            // 複数の差し替えにマッチしたら先に登録されたもの
+            ("owner/app-web", "C_WEB"),
+            ("owner/App-Web", "C_WEB"),
+            ("owner/app-api", "C_APP"),
+            ("owner/lib", "C_ANY"),
+        ];
+        for (repository, expected) in cases {
+            assert_eq!(
+                r.channel_for_repository(repository),
+                expected,
+                "repository={repository:?}"
+            );
+        }
+
+        // 差し替えにマッチしなければルートのチャンネル
+        let mut r = route("owner/*");
+        r.repository_overrides = vec![RepositoryOverride {
+            repository: String::from("app-*"),
+            channel_id: String::from("C_APP"),
+        }];
+        assert_eq!(r.channel_for_repository("owner/lib"), "C_ROUTE");
+        assert_eq!(r.channel_for_repository("owner/app-web"), "C_APP");
+        assert_eq!(route("owner/*").channel_for_repository("owner/app-web"), "C_ROUTE");
+    }
 }
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+
+/// シンプルなglobマッチ
+///
+/// - `*`: `/` を含まない任意の文字列
+/// - `**`: `/` を含む任意の文字列(`**/` は0個以上のディレクトリ)
+/// - `?`: `/` 以外の任意の1文字
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            ['*', '*', rest @ ..] => {
+                if let Some(r) = rest.strip_prefix(&['/']) {
+                    if matches(r, t) {
+                        return true;
+                    }
+                }
+
+                (0..=t.len()).any(|i| matches(rest, &t[i..]))
+            }
+            ['*', rest @ ..] => {
+                for i in 0..=t.len() {
+                    if matches(rest, &t[i..]) {
+                        return true;
+                    }
+                    if t.get(i) == Some(&'/') {
+                        break;
+                    }
+                }
+
+                false
+            }
+            ['?', rest @ ..] => matches!(t, [c, ..] if *c != '/') && matches(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && matches(rest, &t[1..]),
+        }
+    }
+
+    let p = pattern.chars().collect::<Vec<_>>();
+    let t = text.chars().collect::<Vec<_>>();
+    matches(&p, &t)
+}
+
+/// イベントの内容に応じて追加で通知するチャンネルを決めるルール
+///
+/// 指定された条件はすべて満たす必要がある(何も指定しなければすべてのイベントにマッチする)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RoutingRule {
+    pub channel_id: String,
+    /// どれかのラベルがどれかのパターンにマッチすればよい
+    pub labels: Vec<String>,
+    /// PRのマージ先、もしくはpush先のブランチ
+    pub base_branch: Option<String>,
+    /// PRのマージ元、もしくはworkflow_runのブランチ
+    pub head_branch: Option<String>,
+    /// 変更されたファイルのどれかがどれかのパターンにマッチすればよい
+    pub paths: Vec<String>,
+    /// issue/PR/Discussionの作成者(それ以外のイベントは送信者)
+    pub author: Option<String>,
+}
+impl RoutingRule {
+    /// 変更ファイルの一覧(PRの場合はAPIで取得する)が必要か
+    pub fn requires_changed_paths(&self) -> bool {
+        !self.paths.is_empty()
+    }
+
+    pub fn matches(&self, subject: &RoutingSubject) -> bool {
+        if !self.labels.is_empty()
+            && !subject
+                .labels
+                .iter()
+                .any(|l| self.labels.iter().any(|p| glob_match(p, l)))
+        {
+            return false;
+        }
+        if let Some(ref p) = self.base_branch {
+            if !subject.base_branch.is_some_and(|b| glob_match(p, b)) {
+                return false;
+            }
+        }
+        if let Some(ref p) = self.head_branch {
+            if !subject.head_branch.is_some_and(|b| glob_match(p, b)) {
+                return false;
+            }
+        }
+        if !self.paths.is_empty()
+            && !subject
+                .changed_paths
+                .iter()
+                .any(|f| self.paths.iter().any(|p| glob_match(p, f)))
+        {
+            return false;
+        }
+        if let Some(ref a) = self.author {
+            if !subject.author.is_some_and(|x| x.eq_ignore_ascii_case(a)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// ルールの判定に使うイベントの情報
+#[derive(Debug, Default)]
+pub struct RoutingSubject<'s> {
+    pub labels: Vec<&'s str>,
+    pub base_branch: Option<&'s str>,
+    pub head_branch: Option<&'s str>,
+    pub changed_paths: Vec<Cow<'s, str>>,
+    pub author: Option<&'s str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_table() {
+        let cases = [
+            // (pattern, text, expected)
+            ("src/main.rs", "src/main.rs", true),
+            ("src/main.rs", "src/lib.rs", false),
+            ("*.rs", "main.rs", true),
+            ("*.rs", "src/main.rs", false),
+            ("src/*", "src/main.rs", true),
+            ("src/*", "src/github/event.rs", false),
+            ("src/*.rs", "src/.rs", true),
+            ("src/**", "src/github/event.rs", true),
+            ("src/**", "src/", true),
+            ("**/*.rs", "main.rs", true),
+            ("**/*.rs", "src/github/event.rs", true),
+            ("**/*.rs", "src/github/event.toml", false),
+            ("src/**/event.rs", "src/event.rs", true),
+            ("src/**/event.rs", "src/github/v3/event.rs", true),
+            ("src/**/event.rs", "common/src/event.rs", false),
+            ("src/?.rs", "src/a.rs", true),
+            ("src/?.rs", "src/ab.rs", false),
+            ("src?main.rs", "src/main.rs", false),
+            ("*", "", true),
+            ("", "", true),
+            ("", "a", false),
+            // 大文字小文字は区別する(必要なら呼び出し側で揃える)
+            ("README.md", "readme.md", false),
+            ("docs/**", "Docs/index.md", false),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "pattern={pattern:?} text={text:?}");
+        }
+    }
+}
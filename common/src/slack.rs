@@ -1,4 +1,129 @@
-#[derive(serde::Serialize)]
+use std::time::Duration;
+
+pub mod block;
+pub use self::block::Block;
+
+use crate::MessageFormat;
+
+/// Slack Web APIが返すエラー(`ok: false`)
+#[derive(Debug, thiserror::Error)]
+pub enum SlackApiError {
+    #[error("channel_not_found: the channel does not exist or is not visible to the bot")]
+    ChannelNotFound,
+    #[error("not_in_channel: the bot is not a member of the channel")]
+    NotInChannel,
+    #[error("is_archived: the channel has been archived")]
+    IsArchived,
+    #[error("message_not_found: the message to update does not exist")]
+    MessageNotFound,
+    #[error("cant_update_message: the message was not posted by the bot")]
+    CantUpdateMessage,
+    #[error("msg_too_long: the message text is too long")]
+    MessageTooLong,
+    #[error("invalid_attachments: the attachments are malformed")]
+    InvalidAttachments,
+    #[error("invalid_blocks: the blocks are malformed")]
+    InvalidBlocks,
+    #[error("not_authed: no token was provided")]
+    NotAuthed,
+    #[error("invalid_auth: the token is invalid")]
+    InvalidAuth,
+    #[error("token_revoked: the token has been revoked")]
+    TokenRevoked,
+    #[error("account_inactive: the token belongs to a deleted user or workspace")]
+    AccountInactive,
+    #[error("missing_scope: the token lacks a required scope")]
+    MissingScope,
+    #[error("ratelimited: still rate limited after {0} retries")]
+    RateLimited(u32),
+    #[error("Slack API error: {0}")]
+    Other(String),
+    #[error("Slack API response is malformed: {0}")]
+    MalformedResponse(#[from] serde_json::Error),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+}
+impl SlackApiError {
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "channel_not_found" => Self::ChannelNotFound,
+            "not_in_channel" => Self::NotInChannel,
+            "is_archived" => Self::IsArchived,
+            "message_not_found" => Self::MessageNotFound,
+            "cant_update_message" => Self::CantUpdateMessage,
+            "msg_too_long" => Self::MessageTooLong,
+            "invalid_attachments" => Self::InvalidAttachments,
+            "invalid_blocks" => Self::InvalidBlocks,
+            "not_authed" => Self::NotAuthed,
+            "invalid_auth" => Self::InvalidAuth,
+            "token_revoked" => Self::TokenRevoked,
+            "account_inactive" => Self::AccountInactive,
+            "missing_scope" => Self::MissingScope,
+            c => Self::Other(String::from(c)),
+        }
+    }
+}
+
+// 429が返ってきたときにRetry-Afterに従って再試行する回数と、待つ時間の合計の上限
+// (合計はLambdaのタイムアウト(masquerade/function.tf)より十分短くしておく)
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+const MAX_TOTAL_RETRY_WAIT: Duration = Duration::from_secs(8);
+
+async fn call_api<R: serde::de::DeserializeOwned>(
+    method: &str,
+    bot_token: &str,
+    body: &impl serde::Serialize,
+) -> Result<R, SlackApiError> {
+    #[derive(serde::Deserialize)]
+    struct Envelope {
+        ok: bool,
+        error: Option<String>,
+    }
+
+    let mut retries = 0;
+    let mut waited = Duration::ZERO;
+    let resp = loop {
+        let resp = reqwest::Client::new()
+            .post(format!("https://slack.com/api/{method}"))
+            .header(reqwest::header::AUTHORIZATION, format!("Bearer {bot_token}"))
+            .json(body)
+            .send()
+            .await?;
+        if resp.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+            break resp;
+        }
+        if retries >= MAX_RATE_LIMIT_RETRIES {
+            return Err(SlackApiError::RateLimited(retries));
+        }
+
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .map_or(Duration::from_secs(1), Duration::from_secs);
+        if waited + retry_after > MAX_TOTAL_RETRY_WAIT {
+            // 待っている間にLambdaが止められるくらいなら、ここで諦めて失敗を報告する
+            return Err(SlackApiError::RateLimited(retries));
+        }
+        tracing::warn!("Slack API {method} is rate limited; retrying after {retry_after:?}");
+        tokio::time::sleep(retry_after).await;
+        waited += retry_after;
+        retries += 1;
+    };
+
+    let text = resp.error_for_status()?.text().await?;
+    let envelope: Envelope = serde_json::from_str(&text)?;
+    if !envelope.ok {
+        return Err(SlackApiError::from_code(
+            envelope.error.as_deref().unwrap_or("unknown_error"),
+        ));
+    }
+
+    serde_json::from_str(&text).map_err(From::from)
+}
+
+#[derive(serde::Serialize, Clone)]
 pub struct Attachment<'s> {
     pub color: Option<&'s str>,
     pub author_name: Option<&'s str>,
@@ -8,6 +133,12 @@ pub struct Attachment<'s> {
     pub title_link: Option<&'s str>,
     pub text: &'s str,
     pub fields: Vec<AttachmentField<'s>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_id: Option<&'s str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<AttachmentAction<'s>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block<'s>>,
 }
 impl<'s> Attachment<'s> {
     pub const fn new(text: &'s str) -> Self {
@@ -20,6 +151,9 @@ impl<'s> Attachment<'s> {
             title: None,
             title_link: None,
             fields: Vec::new(),
+            callback_id: None,
+            actions: Vec::new(),
+            blocks: Vec::new(),
         }
     }
 
@@ -42,14 +176,71 @@ impl<'s> Attachment<'s> {
         self.fields = fields;
         self
     }
+    pub fn actions(mut self, callback_id: &'s str, actions: Vec<AttachmentAction<'s>>) -> Self {
+        self.callback_id = Some(callback_id);
+        self.actions = actions;
+        self
+    }
+    /// Block Kitで表示するときの中身
+    pub fn blocks(mut self, blocks: Vec<Block<'s>>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// 表示形式に合わせて、使わないほうの中身を外す
+    ///
+    /// Block Kitでは色だけを残してblocksで表示する(blocksがなければattachmentのまま)
+    fn format(self, format: MessageFormat) -> Self {
+        match format {
+            MessageFormat::Attachments => Self {
+                blocks: Vec::new(),
+                ..self
+            },
+            MessageFormat::BlockKit if self.blocks.is_empty() => self,
+            MessageFormat::BlockKit => Self {
+                color: self.color,
+                blocks: self.blocks,
+                ..Self::new("")
+            },
+        }
+    }
 }
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Clone)]
+pub struct AttachmentAction<'s> {
+    pub name: &'s str,
+    pub text: &'s str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<&'s str>,
+}
+impl<'s> AttachmentAction<'s> {
+    pub const fn button(name: &'s str, text: &'s str, value: String) -> Self {
+        Self {
+            name,
+            text,
+            kind: "button",
+            value,
+            style: None,
+        }
+    }
+    pub const fn primary(mut self) -> Self {
+        self.style = Some("primary");
+        self
+    }
+    pub const fn danger(mut self) -> Self {
+        self.style = Some("danger");
+        self
+    }
+}
+#[derive(serde::Serialize, Clone)]
 pub struct AttachmentField<'s> {
     pub title: &'s str,
     pub value: String,
     pub short: bool,
 }
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Clone)]
 pub struct PostMessage<'s> {
     pub channel: &'s str,
     pub text: &'s str,
@@ -57,20 +248,21 @@ pub struct PostMessage<'s> {
     pub unfurl_links: bool,
     pub unfurl_media: bool,
     pub attachments: Vec<Attachment<'s>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<&'s str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub reply_broadcast: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block<'s>>,
+}
+#[derive(serde::Deserialize, Debug)]
+pub struct PostMessageResponse {
+    pub channel: String,
+    pub ts: String,
 }
 impl<'s> PostMessage<'s> {
-    pub async fn post(&self, bot_token: &str) -> reqwest::Result<String> {
-        reqwest::Client::new()
-            .post("https://slack.com/api/chat.postMessage")
-            .header(
-                reqwest::header::AUTHORIZATION,
-                format!("Bearer {bot_token}"),
-            )
-            .json(self)
-            .send()
-            .await?
-            .text()
-            .await
+    pub async fn post(&self, bot_token: &str) -> Result<PostMessageResponse, SlackApiError> {
+        call_api("chat.postMessage", bot_token, self).await
     }
 
     pub const fn new(channel: &'s str, text: &'s str) -> Self {
@@ -81,14 +273,123 @@ impl<'s> PostMessage<'s> {
             unfurl_links: false,
             unfurl_media: false,
             attachments: Vec::new(),
+            thread_ts: None,
+            reply_broadcast: false,
+            blocks: Vec::new(),
         }
     }
     pub const fn as_user(mut self) -> Self {
         self.as_user = true;
         self
     }
+    pub const fn thread(mut self, thread_ts: &'s str) -> Self {
+        self.thread_ts = Some(thread_ts);
+        self
+    }
+    pub const fn reply_broadcast(mut self) -> Self {
+        self.reply_broadcast = true;
+        self
+    }
+    pub fn blocks(mut self, blocks: Vec<Block<'s>>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+    /// 通知先の表示形式で描画する
+    pub fn format(mut self, format: MessageFormat) -> Self {
+        (self.blocks, self.attachments) = format_body(format, self.text, self.attachments);
+        self
+    }
+    pub fn attachments(mut self, attachments: Vec<Attachment<'s>>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+}
+
+/// 投稿済みメッセージの書き換え(chat.update)
+#[derive(serde::Serialize, Clone)]
+pub struct UpdateMessage<'s> {
+    pub channel: &'s str,
+    pub ts: &'s str,
+    pub text: &'s str,
+    pub attachments: Vec<Attachment<'s>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block<'s>>,
+}
+impl<'s> UpdateMessage<'s> {
+    pub async fn post(&self, bot_token: &str) -> Result<PostMessageResponse, SlackApiError> {
+        call_api("chat.update", bot_token, self).await
+    }
+
+    pub const fn new(channel: &'s str, ts: &'s str, text: &'s str) -> Self {
+        Self {
+            channel,
+            ts,
+            text,
+            attachments: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
     pub fn attachments(mut self, attachments: Vec<Attachment<'s>>) -> Self {
         self.attachments = attachments;
         self
     }
+    /// 通知先の表示形式で描画する
+    pub fn format(mut self, format: MessageFormat) -> Self {
+        (self.blocks, self.attachments) = format_body(format, self.text, self.attachments);
+        self
+    }
+}
+
+/// 本文とattachmentsを表示形式に合わせる(`PostMessage` と `UpdateMessage` で共通)
+///
+/// Block Kitでもtextは通知用のフォールバックとして残る
+fn format_body<'s>(
+    format: MessageFormat,
+    text: &'s str,
+    attachments: Vec<Attachment<'s>>,
+) -> (Vec<Block<'s>>, Vec<Attachment<'s>>) {
+    let blocks = match format {
+        MessageFormat::Attachments => Vec::new(),
+        MessageFormat::BlockKit => vec![Block::section(text)],
+    };
+
+    (blocks, attachments.into_iter().map(|a| a.format(format)).collect())
+}
+
+/// インタラクションのresponse_urlに送り返すメッセージ
+#[derive(serde::Serialize)]
+pub struct ResponseMessage<'s, A = Attachment<'s>> {
+    pub text: &'s str,
+    pub replace_original: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_type: Option<&'s str>,
+    pub attachments: Vec<A>,
+}
+impl<'s, A: serde::Serialize> ResponseMessage<'s, A> {
+    pub async fn post(&self, response_url: &str) -> reqwest::Result<String> {
+        reqwest::Client::new()
+            .post(response_url)
+            .json(self)
+            .send()
+            .await?
+            .text()
+            .await
+    }
+
+    pub const fn replace_original(text: &'s str, attachments: Vec<A>) -> Self {
+        Self {
+            text,
+            replace_original: true,
+            response_type: None,
+            attachments,
+        }
+    }
+    pub const fn ephemeral(text: &'s str) -> Self {
+        Self {
+            text,
+            replace_original: false,
+            response_type: Some("ephemeral"),
+            attachments: Vec::new(),
+        }
+    }
 }
@@ -0,0 +1,204 @@
+//! Block Kitのメッセージ構造
+//!
+//! <https://api.slack.com/reference/block-kit/blocks>
+
+use std::borrow::Cow;
+
+// Slack側の制限
+const SECTION_TEXT_MAX_CHARS: usize = 3000;
+const SECTION_FIELDS_MAX: usize = 10;
+const HEADER_TEXT_MAX_CHARS: usize = 150;
+
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text<'s> {
+    PlainText { text: Cow<'s, str>, emoji: bool },
+    Mrkdwn { text: Cow<'s, str> },
+}
+impl<'s> Text<'s> {
+    pub fn plain(text: impl Into<Cow<'s, str>>) -> Self {
+        Self::PlainText {
+            text: text.into(),
+            emoji: true,
+        }
+    }
+    pub fn mrkdwn(text: impl Into<Cow<'s, str>>) -> Self {
+        Self::Mrkdwn { text: text.into() }
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element<'s> {
+    Image {
+        image_url: &'s str,
+        alt_text: &'s str,
+    },
+    Button {
+        text: Text<'s>,
+        action_id: &'s str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<&'s str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<&'s str>,
+    },
+}
+impl<'s> Element<'s> {
+    pub const fn image(image_url: &'s str, alt_text: &'s str) -> Self {
+        Self::Image { image_url, alt_text }
+    }
+    pub fn button(text: &'s str, action_id: &'s str, value: String) -> Self {
+        Self::Button {
+            text: Text::plain(text),
+            action_id,
+            value: Some(value),
+            url: None,
+            style: None,
+        }
+    }
+    /// URLを開くだけのボタン(押されてもインタラクションとしては何もしない)
+    pub fn link_button(text: &'s str, action_id: &'s str, url: &'s str) -> Self {
+        Self::Button {
+            text: Text::plain(text),
+            action_id,
+            value: None,
+            url: Some(url),
+            style: None,
+        }
+    }
+    pub fn primary(self) -> Self {
+        self.styled("primary")
+    }
+    pub fn danger(self) -> Self {
+        self.styled("danger")
+    }
+    fn styled(mut self, s: &'s str) -> Self {
+        if let Self::Button { ref mut style, .. } = self {
+            *style = Some(s);
+        }
+        self
+    }
+}
+
+/// contextブロックの要素(画像かテキスト)
+#[derive(serde::Serialize, Clone)]
+#[serde(untagged)]
+pub enum ContextElement<'s> {
+    Element(Element<'s>),
+    Text(Text<'s>),
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct SectionBlock<'s> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<Text<'s>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Text<'s>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessory: Option<Element<'s>>,
+}
+impl<'s> SectionBlock<'s> {
+    pub fn new(text: Text<'s>) -> Self {
+        Self {
+            text: Some(text),
+            fields: Vec::new(),
+            accessory: None,
+        }
+    }
+    /// mrkdwnのテキストのsection(長すぎるものは切り詰める)
+    pub fn mrkdwn(text: impl Into<Cow<'s, str>>) -> Self {
+        let text = text.into();
+        let text = match text.char_indices().nth(SECTION_TEXT_MAX_CHARS - 1) {
+            Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+            None => text,
+        };
+
+        Self::new(Text::mrkdwn(text))
+    }
+    pub fn fields_only(fields: Vec<Text<'s>>) -> Self {
+        Self {
+            text: None,
+            fields: Vec::new(),
+            accessory: None,
+        }
+        .fields(fields)
+    }
+    /// 2列で並べる項目(多すぎる分は捨てる)
+    pub fn fields(mut self, mut fields: Vec<Text<'s>>) -> Self {
+        fields.truncate(SECTION_FIELDS_MAX);
+        self.fields = fields;
+        self
+    }
+    pub fn accessory(mut self, accessory: Element<'s>) -> Self {
+        self.accessory = Some(accessory);
+        self
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+pub struct ActionsBlock<'s> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_id: Option<&'s str>,
+    pub elements: Vec<Element<'s>>,
+}
+
+#[derive(serde::Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block<'s> {
+    Header {
+        text: Text<'s>,
+    },
+    Section(SectionBlock<'s>),
+    Context {
+        elements: Vec<ContextElement<'s>>,
+    },
+    Divider,
+    Actions(ActionsBlock<'s>),
+    Image {
+        image_url: &'s str,
+        alt_text: &'s str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<Text<'s>>,
+    },
+}
+impl<'s> Block<'s> {
+    /// 見出し(plain_textのみで、長すぎるものは切り詰める)
+    pub fn header(text: impl Into<Cow<'s, str>>) -> Self {
+        let text = text.into();
+        let text = match text.char_indices().nth(HEADER_TEXT_MAX_CHARS - 1) {
+            Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+            None => text,
+        };
+
+        Self::Header {
+            text: Text::plain(text),
+        }
+    }
+    /// mrkdwnのテキストだけのsection(長すぎるものは切り詰める)
+    pub fn section(text: impl Into<Cow<'s, str>>) -> Self {
+        Self::Section(SectionBlock::mrkdwn(text))
+    }
+    pub const fn context(elements: Vec<ContextElement<'s>>) -> Self {
+        Self::Context { elements }
+    }
+    pub const fn divider() -> Self {
+        Self::Divider
+    }
+    pub const fn actions(block_id: Option<&'s str>, elements: Vec<Element<'s>>) -> Self {
+        Self::Actions(ActionsBlock { block_id, elements })
+    }
+    pub const fn image(image_url: &'s str, alt_text: &'s str) -> Self {
+        Self::Image {
+            image_url,
+            alt_text,
+            title: None,
+        }
+    }
+}
+impl<'s> From<SectionBlock<'s>> for Block<'s> {
+    fn from(s: SectionBlock<'s>) -> Self {
+        Self::Section(s)
+    }
+}
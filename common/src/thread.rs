@@ -0,0 +1,97 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::RouteReadWriteError;
+
+/// スレッドにまとめる対象(issue/PRは番号を共有するので同じ扱い)
+#[derive(Clone, Copy)]
+pub enum ThreadSubject<'s> {
+    Issue { repository: &'s str, number: usize },
+    Discussion { repository: &'s str, number: usize },
+}
+impl ThreadSubject<'_> {
+    pub fn key(&self, channel_id: &str) -> String {
+        match self {
+            Self::Issue { repository, number } => format!("{channel_id}:{repository}#{number}"),
+            Self::Discussion { repository, number } => format!("{channel_id}:{repository}/discussions/{number}"),
+        }
+    }
+}
+
+/// 各issue/PR/Discussionについて最初に投稿したメッセージ(スレッドの親)
+pub struct MessageThread {
+    pub channel_id: String,
+    pub ts: String,
+    /// 親がissue/PR/Discussionを開いたときの通知であればその本文(あとから書き換えるときに使う)
+    pub root_text: Option<String>,
+}
+impl MessageThread {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-Threads";
+
+    pub async fn get(client: &aws_sdk_dynamodb::Client, key: String) -> Result<Option<Self>, RouteReadWriteError> {
+        let Some(mut item) = client
+            .get_item()
+            .table_name(Self::TABLE_NAME)
+            .key("thread_key", AttributeValue::S(key))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?
+            .item
+        else {
+            return Ok(None);
+        };
+
+        let channel_id = match item.remove("channel_id") {
+            Some(AttributeValue::S(x)) => x,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("channel_id")),
+            None => return Err(RouteReadWriteError::KeyNotFound("channel_id")),
+        };
+        let ts = match item.remove("ts") {
+            Some(AttributeValue::S(x)) => x,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("ts")),
+            None => return Err(RouteReadWriteError::KeyNotFound("ts")),
+        };
+
+        let root_text = match item.remove("root_text") {
+            Some(AttributeValue::S(x)) => Some(x),
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("root_text")),
+            None => None,
+        };
+
+        Ok(Some(Self {
+            channel_id,
+            ts,
+            root_text,
+        }))
+    }
+
+    /// すでに親が記録されていれば上書きしない(先に投稿されたほうを親とする)
+    pub async fn put_if_absent(
+        self,
+        client: &aws_sdk_dynamodb::Client,
+        key: String,
+    ) -> Result<(), RouteReadWriteError> {
+        let mut req = client
+            .put_item()
+            .table_name(Self::TABLE_NAME)
+            .item("thread_key", AttributeValue::S(key))
+            .item("channel_id", AttributeValue::S(self.channel_id))
+            .item("ts", AttributeValue::S(self.ts))
+            .condition_expression("attribute_not_exists(thread_key)");
+        if let Some(t) = self.root_text {
+            req = req.item("root_text", AttributeValue::S(t));
+        }
+
+        let r = req.send().await;
+
+        match r {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(aws_sdk_dynamodb::Error::from(e).into()),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+
+use crate::{github::oauth::UserAccessToken, RouteReadWriteError};
+
+/// SlackユーザーとGitHubアカウントの対応
+///
+/// GitHubの認可フローで本人が連携したものだけを記録する
+pub struct UserMapping {
+    pub slack_user_id: String,
+    pub github_login: String,
+    /// 本人として操作するためのユーザーアクセストークン(認可フロー導入前の記録にはない)
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    /// アクセストークンの期限(UNIX時刻)。期限のないトークンならNone
+    pub token_expires_at: Option<i64>,
+}
+impl UserMapping {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-UserMap";
+
+    pub async fn get(
+        client: &aws_sdk_dynamodb::Client,
+        slack_user_id: String,
+    ) -> Result<Option<Self>, RouteReadWriteError> {
+        let Some(mut item) = client
+            .get_item()
+            .table_name(Self::TABLE_NAME)
+            .key("slack_user_id", AttributeValue::S(slack_user_id.clone()))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?
+            .item
+        else {
+            return Ok(None);
+        };
+
+        let github_login = match item.remove("github_login") {
+            Some(AttributeValue::S(x)) => x,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("github_login")),
+            None => return Err(RouteReadWriteError::KeyNotFound("github_login")),
+        };
+        let access_token = match item.remove("access_token") {
+            Some(AttributeValue::S(x)) => Some(x),
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("access_token")),
+            None => None,
+        };
+        let refresh_token = match item.remove("refresh_token") {
+            Some(AttributeValue::S(x)) => Some(x),
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotString("refresh_token")),
+            None => None,
+        };
+        let token_expires_at = match item.remove("token_expires_at") {
+            Some(AttributeValue::N(x)) => Some(
+                x.parse()
+                    .map_err(|_| RouteReadWriteError::InvalidValue("token_expires_at", x))?,
+            ),
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotNumber("token_expires_at")),
+            None => None,
+        };
+
+        Ok(Some(Self {
+            slack_user_id,
+            github_login,
+            access_token,
+            refresh_token,
+            token_expires_at,
+        }))
+    }
+
+    pub async fn put(&self, client: &aws_sdk_dynamodb::Client) -> Result<(), RouteReadWriteError> {
+        let mut req = client
+            .put_item()
+            .table_name(Self::TABLE_NAME)
+            .item("slack_user_id", AttributeValue::S(self.slack_user_id.clone()))
+            .item("github_login", AttributeValue::S(self.github_login.clone()));
+        if let Some(ref t) = self.access_token {
+            req = req.item("access_token", AttributeValue::S(t.clone()));
+        }
+        if let Some(ref t) = self.refresh_token {
+            req = req.item("refresh_token", AttributeValue::S(t.clone()));
+        }
+        if let Some(t) = self.token_expires_at {
+            req = req.item("token_expires_at", AttributeValue::N(t.to_string()));
+        }
+        req.send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// 認可フローで受け取ったトークンに差し替える
+    pub fn set_token(&mut self, token: UserAccessToken) {
+        self.token_expires_at = token
+            .expires_in
+            .map(|s| time::OffsetDateTime::now_utc().unix_timestamp() + s);
+        self.access_token = Some(token.access_token);
+        self.refresh_token = token.refresh_token;
+    }
+
+    /// アクセストークンが使えないか、もうすぐ期限が切れる
+    pub fn token_expired(&self) -> bool {
+        // 呼び出している間に切れないように少し余裕をみる
+        let now = time::OffsetDateTime::now_utc().unix_timestamp() + 60;
+
+        self.access_token.is_none() || self.token_expires_at.is_some_and(|t| t <= now)
+    }
+}
+
+/// GitHubの認可フローを始めたSlackユーザー(認可画面に渡すstateに対応する)
+pub struct OAuthState;
+impl OAuthState {
+    const TABLE_NAME: &'static str = "Masquerade-GithubActivityNotification-OAuthStates";
+    /// 認可画面から戻ってくるまでの猶予
+    const LIFETIME: time::Duration = time::Duration::minutes(10);
+
+    pub async fn put(
+        client: &aws_sdk_dynamodb::Client,
+        state: String,
+        slack_user_id: String,
+    ) -> Result<(), RouteReadWriteError> {
+        let expires_at = time::OffsetDateTime::now_utc() + Self::LIFETIME;
+
+        client
+            .put_item()
+            .table_name(Self::TABLE_NAME)
+            .item("state", AttributeValue::S(state))
+            .item("slack_user_id", AttributeValue::S(slack_user_id))
+            .item("expires_at", AttributeValue::N(expires_at.unix_timestamp().to_string()))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    /// stateを使い切って、認可フローを始めたSlackユーザーを返す
+    ///
+    /// 知らないstateや期限切れのものはNone(TTLでの削除は遅れることがあるので期限もここで見る)
+    pub async fn consume(
+        client: &aws_sdk_dynamodb::Client,
+        state: String,
+    ) -> Result<Option<String>, RouteReadWriteError> {
+        let Some(mut item) = client
+            .delete_item()
+            .table_name(Self::TABLE_NAME)
+            .key("state", AttributeValue::S(state))
+            .return_values(ReturnValue::AllOld)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?
+            .attributes
+        else {
+            return Ok(None);
+        };
+
+        let expires_at: i64 = match item.remove("expires_at") {
+            Some(AttributeValue::N(x)) => x
+                .parse()
+                .map_err(|_| RouteReadWriteError::InvalidValue("expires_at", x))?,
+            Some(_) => return Err(RouteReadWriteError::ValueIsNotNumber("expires_at")),
+            None => return Err(RouteReadWriteError::KeyNotFound("expires_at")),
+        };
+        if expires_at <= time::OffsetDateTime::now_utc().unix_timestamp() {
+            return Ok(None);
+        }
+
+        match item.remove("slack_user_id") {
+            Some(AttributeValue::S(x)) => Ok(Some(x)),
+            Some(_) => Err(RouteReadWriteError::ValueIsNotString("slack_user_id")),
+            None => Err(RouteReadWriteError::KeyNotFound("slack_user_id")),
+        }
+    }
+}
@@ -21,6 +21,10 @@ variable "routemap_table_arn" {
   type = string
 }
 
+variable "webhook_base_url" {
+  type = string
+}
+
 locals {
   function_name = "Masquerade-GithubActivityNotification-Configurator"
 }
@@ -35,11 +39,14 @@ resource "aws_lambda_function" "function" {
   handler          = "hello.handler"
   runtime          = "provided.al2023"
   architectures    = ["arm64"]
+  # Slackへの応答はすぐに返して、処理は非同期に呼び出し直した先でやる(GitHub APIの呼び出しを含むので長めにとる)
+  timeout = 30
 
   environment {
     variables = {
-      RUST_LOG       = var.enable_debug_log ? "trace" : "error"
-      RUST_BACKTRACE = 1
+      RUST_LOG         = var.enable_debug_log ? "trace" : "error"
+      RUST_BACKTRACE   = 1
+      WEBHOOK_BASE_URL = var.webhook_base_url
     }
   }
 
@@ -49,6 +56,12 @@ resource "aws_lambda_function" "function" {
   ]
 }
 
+# 非同期呼び出しの再試行でルートの作成などを二重にしない
+resource "aws_lambda_function_event_invoke_config" "deferred_invocation" {
+  function_name          = aws_lambda_function.function.function_name
+  maximum_retry_attempts = 0
+}
+
 resource "aws_lambda_permission" "invocation_permission" {
   function_name = aws_lambda_function.function.function_name
   action        = "lambda:InvokeFunction"
@@ -70,6 +83,13 @@ resource "aws_apigatewayv2_route" "route" {
   target    = "integrations/${aws_apigatewayv2_integration.api_lambda_integration.id}"
 }
 
+# GitHub Appのユーザー認可のコールバック(AppのCallback URLにこのパスを設定する)
+resource "aws_apigatewayv2_route" "oauth_callback_route" {
+  api_id    = var.api_id
+  route_key = "GET ${var.base_path}/oauth/callback"
+  target    = "integrations/${aws_apigatewayv2_integration.api_lambda_integration.id}"
+}
+
 resource "aws_iam_role" "execution_role" {
   name = "${local.function_name}-ExecutionRole"
   path = "/service-role/webhook/masquerade/github-activity/configurator/"
@@ -118,6 +138,21 @@ resource "aws_iam_policy" "secret_read_policy" {
   })
 }
 
+resource "aws_iam_policy" "route_secret_write_policy" {
+  name = "${local.function_name}-LambdaRouteSecretWritePolicy"
+  path = "/webhook/PeridotGithubActivity/configurator/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["secretsmanager:CreateSecret", "secretsmanager:DeleteSecret"],
+        Resource = "arn:aws:secretsmanager:${data.aws_region.current.name}:${data.aws_caller_identity.current.account_id}:secret:repoact-notify/routes/*"
+      }
+    ]
+  })
+}
+
 resource "aws_iam_policy" "routemap_write_policy" {
   name = "${local.function_name}-LambdaRouteMapWritePolicy"
   path = "/webhook/PeridotGithubActivity/configurator/"
@@ -126,8 +161,53 @@ resource "aws_iam_policy" "routemap_write_policy" {
     Statement = [
       {
         Effect   = "Allow",
-        Action   = "dynamodb:PutItem",
-        Resource = var.routemap_table_arn
+        Action   = ["dynamodb:GetItem", "dynamodb:PutItem", "dynamodb:DeleteItem", "dynamodb:Query", "dynamodb:Scan"],
+        Resource = [var.routemap_table_arn, "${var.routemap_table_arn}/index/*"]
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "usermap_readwrite_policy" {
+  name = "${local.function_name}-LambdaUserMapReadWritePolicy"
+  path = "/webhook/PeridotGithubActivity/configurator/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:GetItem", "dynamodb:PutItem"],
+        Resource = aws_dynamodb_table.usermap.arn
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "self_invoke_policy" {
+  name = "${local.function_name}-LambdaSelfInvokePolicy"
+  path = "/webhook/PeridotGithubActivity/configurator/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = "lambda:InvokeFunction",
+        Resource = aws_lambda_function.function.arn
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "oauth_states_readwrite_policy" {
+  name = "${local.function_name}-LambdaOAuthStatesReadWritePolicy"
+  path = "/webhook/PeridotGithubActivity/configurator/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:PutItem", "dynamodb:DeleteItem"],
+        Resource = aws_dynamodb_table.oauth_states.arn
       }
     ]
   })
@@ -143,18 +223,68 @@ resource "aws_iam_role_policy_attachment" "execution_role_secret_read_policy" {
   policy_arn = aws_iam_policy.secret_read_policy.arn
 }
 
+resource "aws_iam_role_policy_attachment" "execution_role_route_secret_write_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.route_secret_write_policy.arn
+}
+
 resource "aws_iam_role_policy_attachment" "execution_role_routemap_write_policy" {
   role       = aws_iam_role.execution_role.name
   policy_arn = aws_iam_policy.routemap_write_policy.arn
 }
 
+resource "aws_iam_role_policy_attachment" "execution_role_usermap_readwrite_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.usermap_readwrite_policy.arn
+}
+
+resource "aws_iam_role_policy_attachment" "execution_role_self_invoke_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.self_invoke_policy.arn
+}
+
+resource "aws_iam_role_policy_attachment" "execution_role_oauth_states_readwrite_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.oauth_states_readwrite_policy.arn
+}
+
 resource "aws_cloudwatch_log_group" "function_log_group" {
   name              = "/aws/lambda/${local.function_name}"
   retention_in_days = 1
 }
 
+resource "aws_dynamodb_table" "usermap" {
+  name         = "Masquerade-GithubActivityNotification-UserMap"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "slack_user_id"
+
+  attribute {
+    name = "slack_user_id"
+    type = "S"
+  }
+}
+
+resource "aws_dynamodb_table" "oauth_states" {
+  name         = "Masquerade-GithubActivityNotification-OAuthStates"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "state"
+
+  attribute {
+    name = "state"
+    type = "S"
+  }
+
+  ttl {
+    attribute_name = "expires_at"
+    enabled        = true
+  }
+}
+
 # externally defined resources
 
+data "aws_region" "current" {}
+data "aws_caller_identity" "current" {}
+
 data "aws_secretsmanager_secret" "secrets" {
   name = "repoact-notify"
 }
@@ -0,0 +1,61 @@
+//! Slackの3秒制限に収まらない処理を応答のあとに回すために、自分自身を非同期に呼び出す
+
+use aws_credential_types::provider::{error::CredentialsError, ProvideCredentials};
+use aws_sigv4::{
+    http_request::{sign, SignableBody, SignableRequest, SigningError, SigningSettings},
+    sign::v4,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvokeError {
+    #[error("{0} is not configured")]
+    MissingConfiguration(&'static str),
+    #[error(transparent)]
+    Credentials(#[from] CredentialsError),
+    #[error(transparent)]
+    SigningParams(#[from] v4::signing_params::BuildError),
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+    #[error(transparent)]
+    Serialize(#[from] serde_json::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+/// 実行中のLambda関数をInvocationType=Eventで呼び出す(終わるのは待たない)
+pub async fn invoke_self_async<T: serde::Serialize>(
+    sdk_config: &aws_config::SdkConfig,
+    payload: &T,
+) -> Result<(), InvokeError> {
+    let function_name =
+        std::env::var("AWS_LAMBDA_FUNCTION_NAME").map_err(|_| InvokeError::MissingConfiguration("function name"))?;
+    let region = sdk_config.region().ok_or(InvokeError::MissingConfiguration("region"))?;
+    let identity = sdk_config
+        .credentials_provider()
+        .ok_or(InvokeError::MissingConfiguration("credentials provider"))?
+        .provide_credentials()
+        .await?
+        .into();
+
+    let url = format!("https://lambda.{region}.amazonaws.com/2015-03-31/functions/{function_name}/invocations");
+    let body = serde_json::to_vec(payload)?;
+    let headers = [("content-type", "application/json"), ("x-amz-invocation-type", "Event")];
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region.as_ref())
+        .name("lambda")
+        .time(std::time::SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()?
+        .into();
+    let signable = SignableRequest::new("POST", &url, headers.iter().copied(), SignableBody::Bytes(&body))?;
+    let (instructions, _) = sign(signable, &params)?.into_parts();
+
+    let mut req = reqwest::Client::new().post(&url);
+    for (k, v) in headers.iter().copied().chain(instructions.headers()) {
+        req = req.header(k, v);
+    }
+    req.body(body).send().await?.error_for_status()?;
+
+    Ok(())
+}
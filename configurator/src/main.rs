@@ -2,13 +2,18 @@ use std::{borrow::Cow, collections::HashMap};
 
 use lambda_runtime::LambdaEvent;
 use nom::Parser;
-use repoact_notify_common::{slack, Route};
+use repoact_notify_common::{
+    deployment_review, github, slack, ChannelTarget, EventFilter, EventKind, MessageFormat, OAuthState,
+    RepositoryOverride, Route, RoutingRule, UserMapping, WorkflowRunNotifyMode,
+};
 use ring::{
     constant_time,
     hmac::{self, HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod invoke;
 mod secrets;
 
 #[tokio::main]
@@ -29,6 +34,34 @@ pub struct GatewayRequest<H = HashMap<String, String>> {
     pub is_base64_encoded: bool,
 }
 
+/// 応答したあとに処理するリクエスト(署名は検証済み)
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DeferredRequest {
+    pub deferred_body: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum ConfiguratorEvent {
+    /// `invoke::invoke_self_async` で自分自身から呼ばれた
+    Deferred(DeferredRequest),
+    /// GitHubの認可画面から戻ってきた
+    OAuthCallback(OAuthCallbackRequest),
+    Gateway(GatewayRequest<SlackRequestHeaders>),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthCallbackRequest {
+    pub query_string_parameters: OAuthCallbackQuery,
+}
+
+#[derive(serde::Deserialize)]
+pub struct OAuthCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct SlackRequestHeaders {
@@ -39,13 +72,103 @@ pub struct SlackRequestHeaders {
 #[derive(serde::Deserialize)]
 pub struct SlackSlashCommandPayload {
     pub channel_id: String,
+    pub user_id: String,
     pub text: String,
     pub command: String,
+    pub response_url: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackInteractionForm {
+    pub payload: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackInteractionUser {
+    pub id: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackInteractionAction {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackOriginalMessage {
+    pub text: String,
+    #[serde(default)]
+    pub attachments: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackInteractiveMessagePayload {
+    pub callback_id: String,
+    pub actions: Vec<SlackInteractionAction>,
+    pub user: SlackInteractionUser,
+    pub response_url: String,
+    pub original_message: SlackOriginalMessage,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackBlockAction {
+    pub action_id: String,
+    pub block_id: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SlackBlockActionsPayload {
+    pub actions: Vec<SlackBlockAction>,
+    pub user: SlackInteractionUser,
+    pub response_url: String,
+    pub message: SlackOriginalMessage,
+}
+impl From<SlackBlockActionsPayload> for SlackInteractiveMessagePayload {
+    fn from(p: SlackBlockActionsPayload) -> Self {
+        // Block Kitではblock_idがcallback_id、action_idがnameに相当する
+        // リンクボタンはURLを開くだけなので処理対象にしない
+        let actions = p.actions.into_iter().filter(|a| a.url.is_none()).collect::<Vec<_>>();
+        Self {
+            callback_id: actions.first().map(|a| a.block_id.clone()).unwrap_or_default(),
+            actions: actions
+                .into_iter()
+                .map(|a| SlackInteractionAction {
+                    name: a.action_id,
+                    value: a.value,
+                })
+                .collect(),
+            user: p.user,
+            response_url: p.response_url,
+            original_message: p.message,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SlackInteractionPayload {
+    InteractiveMessage(SlackInteractiveMessagePayload),
+    BlockActions(SlackBlockActionsPayload),
+}
+impl From<SlackInteractionPayload> for SlackInteractiveMessagePayload {
+    fn from(p: SlackInteractionPayload) -> Self {
+        match p {
+            SlackInteractionPayload::InteractiveMessage(p) => p,
+            SlackInteractionPayload::BlockActions(p) => p.into(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ProcessError {
     SlackRequestValidationFailed(String, String),
+    StaleSlackRequest(String),
+    UnknownInteraction(String, Option<String>),
+    MissingResponseField(&'static str),
 }
 impl std::error::Error for ProcessError {}
 impl std::fmt::Display for ProcessError {
@@ -54,6 +177,9 @@ impl std::fmt::Display for ProcessError {
             Self::SlackRequestValidationFailed(c, e) => {
                 write!(f, "Invalid request: computed={c:?} expected={e:?}")
             }
+            Self::StaleSlackRequest(t) => write!(f, "Stale request: timestamp={t:?}"),
+            Self::UnknownInteraction(c, a) => write!(f, "Unknown interaction: callback_id={c:?} action={a:?}"),
+            Self::MissingResponseField(k) => write!(f, "Field {k:?} is not contained in the response"),
         }
     }
 }
@@ -62,76 +188,933 @@ impl std::fmt::Display for ProcessError {
 pub enum ParseError {
     #[error("Syntax error: {0}")]
     SyntaxError(nom::Err<nom::error::Error<String>>),
+    #[error("Unexpected input: {0:?}")]
+    UnexpectedInput(String),
     #[error("Unrecognized command: {0}")]
     UnrecognizedCommand(String),
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+    #[error("Invalid value for option {0}: {1:?}")]
+    InvalidOptionValue(String, String),
 }
 
-async fn handler(e: LambdaEvent<GatewayRequest<SlackRequestHeaders>>) -> Result<String, lambda_runtime::Error> {
+async fn handler(e: LambdaEvent<ConfiguratorEvent>) -> Result<String, lambda_runtime::Error> {
     let sdk_config = aws_config::load_from_env().await;
 
     let (msq_secrets, service_secrets) = secrets::load(&sdk_config).await?;
-    let body = if e.payload.is_base64_encoded {
-        String::from_utf8(base64::decode(e.payload.body)?)?
+    let req = match e.payload {
+        ConfiguratorEvent::Gateway(req) => req,
+        ConfiguratorEvent::Deferred(req) => {
+            // 非同期呼び出しは失敗すると再試行されるので、エラーはここで止める
+            if let Err(e) = process_deferred(&sdk_config, &msq_secrets, &service_secrets, req.deferred_body).await {
+                tracing::error!("Failed to process deferred request: {e}");
+            }
+
+            return Ok(String::new());
+        }
+        ConfiguratorEvent::OAuthCallback(req) => {
+            // ブラウザに表示されるので、失敗の詳細はログにだけ出す
+            return Ok(
+                match process_oauth_callback(&sdk_config, &msq_secrets, req.query_string_parameters).await {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        tracing::error!("Failed to link GitHub account: {e}");
+                        String::from("GitHubアカウントの連携に失敗しちゃった。Slackからもう一度やり直してね。")
+                    }
+                },
+            );
+        }
+    };
+    let body = if req.is_base64_encoded {
+        String::from_utf8(base64::decode(req.body)?)?
     } else {
-        e.payload.body
+        req.body
     };
 
     verify_slack_command_request(
         &body,
-        &e.payload.headers.x_slack_request_timestamp,
+        &req.headers.x_slack_request_timestamp,
         &msq_secrets.slack_app_signing_secret,
-        e.payload.headers.x_slack_signature,
+        req.headers.x_slack_signature,
     )?;
 
+    // Slackは3秒以内に応答しないとタイムアウトにするので、すぐに応答して
+    // 実際の処理は自分自身を呼び出し直した先でやる(結果はresponse_urlに送る)
+    invoke::invoke_self_async(&sdk_config, &DeferredRequest { deferred_body: body }).await?;
+
+    Ok(String::new())
+}
+
+async fn process_deferred(
+    sdk_config: &aws_config::SdkConfig,
+    msq_secrets: &secrets::MasqueradeConfiguratorSecrets,
+    service_secrets: &secrets::ServiceSecrets,
+    body: String,
+) -> Result<(), lambda_runtime::Error> {
+    if let Ok(form) = serde_urlencoded::from_str::<SlackInteractionForm>(&body) {
+        // インタラクティブメッセージのボタン操作
+        let payload: SlackInteractiveMessagePayload =
+            serde_json::from_str::<SlackInteractionPayload>(&form.payload)?.into();
+        if payload.actions.is_empty() {
+            // リンクボタンのクリック通知は何もしない
+            return Ok(());
+        }
+        let response_url = payload.response_url.clone();
+        let r = match &payload.callback_id as &str {
+            deployment_review::CALLBACK_ID => {
+                process_pending_deployment_review(sdk_config, msq_secrets, service_secrets, payload).await
+            }
+            _ => Err(ProcessError::UnknownInteraction(
+                payload.callback_id,
+                payload.actions.into_iter().next().map(|a| a.name),
+            )
+            .into()),
+        };
+        if let Err(e) = r {
+            tracing::error!("Failed to process interaction: {e}");
+            slack::ResponseMessage::<slack::Attachment>::ephemeral(
+                "処理に失敗しちゃった。時間をおいてもう一度試してみてね。",
+            )
+            .post(&response_url)
+            .await?;
+        }
+
+        return Ok(());
+    }
+
     let payload: SlackSlashCommandPayload = serde_urlencoded::from_str(&body)?;
+    let response_url = payload.response_url.clone();
+    let command = payload.command.clone();
+    let reply = match process_command(sdk_config, msq_secrets, service_secrets, payload).await {
+        Ok(r) => r,
+        Err(e) => match e.downcast_ref::<ParseError>() {
+            Some(e) => e.to_string(),
+            None => {
+                tracing::error!("Failed to process command {command}: {e}");
+                String::from("コマンドの処理に失敗しちゃった。時間をおいてもう一度試してみてね。")
+            }
+        },
+    };
+    if !reply.is_empty() {
+        // スラッシュコマンドの結果は実行した本人にだけ見せる
+        slack::ResponseMessage::<slack::Attachment>::ephemeral(&reply)
+            .post(&response_url)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn process_command(
+    sdk_config: &aws_config::SdkConfig,
+    msq_secrets: &secrets::MasqueradeConfiguratorSecrets,
+    service_secrets: &secrets::ServiceSecrets,
+    payload: SlackSlashCommandPayload,
+) -> Result<String, lambda_runtime::Error> {
     let args = match &payload.command as &str {
-        "/add-repoact-notify" => {
-            parse_add_args(&payload.text)
-                .map_err(|e| ParseError::SyntaxError(e.map_input(ToOwned::to_owned)))?
+        "/add-repoact-notify" => parse_add_args(payload.text.trim()).map_err(ParseError::from)?.1,
+        "/add-repoact-notify-rule" => parse_add_rule_args(payload.text.trim()).map_err(ParseError::from)?.1,
+        "/add-repoact-notify-target" => parse_add_target_args(payload.text.trim()).map_err(ParseError::from)?.1,
+        "/add-repoact-notify-override" => {
+            parse_add_override_args(payload.text.trim())
+                .map_err(ParseError::from)?
+                .1
+        }
+        "/repoact-notify" => {
+            parse_repoact_notify_args(payload.text.trim())
+                .map_err(ParseError::from)?
                 .1
         }
+        "/link-repoact-github" => Args::LinkGitHub,
         _ => return Err(ParseError::UnrecognizedCommand(payload.command).into()),
     };
 
     match args {
-        Args::Add { repo_fullname, path } => {
+        Args::Add {
+            repo_fullname,
+            path,
+            options,
+        } => {
+            // Webhookを作れるのは管理者か、リポジトリ(Organization)の管理者として連携済みのユーザーだけ
+            if !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+                let Some(mapping) = UserMapping::get(&dynamodb, payload.user_id.clone()).await? else {
+                    return Ok(String::from(
+                        "GitHubアカウントがまだ連携されていないみたい。`/link-repoact-github` で連携してから登録してね!",
+                    ));
+                };
+                let is_admin = github::ApiClient::new(
+                    &service_secrets.github_app_id,
+                    &service_secrets.github_app_installation_id,
+                    &service_secrets.github_app_pem,
+                    &repo_fullname,
+                )
+                .await?
+                .is_admin(&mapping.github_login)
+                .await?;
+                if !is_admin {
+                    return Ok(format!(
+                        "{}さんは{repo_fullname}の管理者じゃないから、ルートを登録できないよ。",
+                        mapping.github_login
+                    ));
+                }
+            }
+
             // prebuild message
             let msg = format!("これから<https://github.com/{repo_fullname}|{repo_fullname}>の状況をこのチャンネルに通知していくよ!よろしくね!");
 
-            Route {
-                repository_fullpath: repo_fullname.into_owned(),
+            let mut route = Route {
+                repository_fullpath: repo_fullname.clone().into_owned(),
                 channel_id: payload.channel_id.clone(),
+                workflow_run_notify: WorkflowRunNotifyMode::default(),
+                broadcast_state_changes: false,
+                message_format: MessageFormat::default(),
+                events: EventFilter::default(),
+                rules: Vec::new(),
+                targets: Vec::new(),
+                repository_overrides: Vec::new(),
+                include_repositories: Vec::new(),
+                exclude_repositories: Vec::new(),
+                secret_arn: None,
+                hook_id: None,
+                legacy_signature: false,
+            };
+            for (k, v) in options {
+                apply_route_option(&mut route, k, &v)?;
             }
-            .put(&aws_sdk_dynamodb::Client::new(&sdk_config), path.into_owned())
-            .await?;
+            let path = match path {
+                Some(p) => p.into_owned(),
+                None => generate_route_id()?,
+            };
+            let events = route.subscribed_events();
+
+            // ルート専用のWebhookシークレットを作る
+            let secretsmanager = aws_sdk_secretsmanager::Client::new(sdk_config);
+            let webhook_secret = random_hex(32)?;
+            let secret_name = format!("{}{path}", Route::SECRET_NAME_PREFIX);
+            let created = secretsmanager
+                .create_secret()
+                .name(&secret_name)
+                .description(format!("repoact-notify webhook secret for {repo_fullname}"))
+                .secret_string(&webhook_secret)
+                .send()
+                .await;
+            let secret_arn = match created {
+                Ok(r) => r.arn,
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_resource_exists_exception()) => {
+                    return Ok(format!(
+                        "ルート {path:?} はもう使われているみたい。別のパスを指定するか、省略して自動で決めてね。"
+                    ));
+                }
+                // 削除待ちのシークレットが残っているとInvalidRequestExceptionになる
+                Err(e) if e.as_service_error().is_some_and(|e| e.is_invalid_request_exception()) => {
+                    return Ok(format!(
+                        "ルート {path:?} のシークレットがまだ削除待ちになっているみたい。別のパスを指定するか、省略して自動で決めてね。"
+                    ));
+                }
+                Err(e) => return Err(aws_sdk_secretsmanager::Error::from(e).into()),
+            };
+
+            // 他のチームのルートを上書きしないようにする
+            let stored = async {
+                route.secret_arn = Some(secret_arn.ok_or(ProcessError::MissingResponseField("ARN"))?);
+                route
+                    .put_if_absent(&aws_sdk_dynamodb::Client::new(sdk_config), path.clone())
+                    .await
+                    .map_err(lambda_runtime::Error::from)
+            };
+            match stored.await {
+                Ok(true) => (),
+                // ルートを登録できなかったら、作ったシークレットは使われないので消しておく
+                r => {
+                    delete_route_secret(&secretsmanager, &secret_name).await;
+                    r?;
+                    return Ok(format!(
+                        "ルート {path:?} はもう使われているみたい。別のパスを指定するか、省略して自動で決めてね。"
+                    ));
+                }
+            }
+
+            // ルートはもう登録できているので、ここで失敗してもコマンドは失敗させない
+            if let Err(e) = slack::PostMessage::new(&payload.channel_id, &msg)
+                .as_user()
+                .post(&service_secrets.slack_bot_token)
+                .await
+            {
+                tracing::error!("Failed to post the greeting for {repo_fullname}: {e}");
+            }
+
+            // シークレットは実行した本人にだけ返す返信以外には載せない
+            let Some(url) = webhook_url(&path) else {
+                return Ok(webhook_setup_instructions(&repo_fullname, &path, &webhook_secret));
+            };
+            let events = events.iter().map(|k| k.as_str()).collect::<Vec<_>>();
+            let registration = register_webhook(service_secrets, &repo_fullname, &url, &webhook_secret, &events).await;
+            return Ok(match registration {
+                Ok(r) => {
+                    let done = match r {
+                        github::WebhookRegistration::Created(_) => "作成",
+                        github::WebhookRegistration::Updated(_) => "更新",
+                    };
+                    format!("ルート `{path}` を登録して、GitHubのWebhook(`{url}`)も{done}したよ!")
+                }
+                Err(e) => {
+                    tracing::error!("webhook registration failed for {repo_fullname}: {e}");
+                    format!(
+                        "Webhookの自動登録に失敗したみたい({e})。\n{}",
+                        webhook_setup_instructions(&repo_fullname, &path, &webhook_secret)
+                    )
+                }
+            });
+        }
+        Args::AddRule { path, options } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let Some(mut route) = Route::get(&dynamodb, path.clone().into_owned()).await? else {
+                return Ok(format!("ルート {path:?} はまだ登録されていないみたい。"));
+            };
+            if route.channel_id != payload.channel_id && !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                return Ok(format!(
+                    "ルート {path:?} はこのチャンネルのものじゃないからルールを追加できないよ。"
+                ));
+            }
+
+            let mut rule = RoutingRule {
+                channel_id: payload.channel_id.clone(),
+                ..Default::default()
+            };
+            for (k, v) in options {
+                apply_rule_option(&mut rule, k, &v)?;
+            }
+            route.rules.push(rule);
+            let repo_fullname = route.repository_fullpath.clone();
+            route.put(&dynamodb, path.into_owned()).await?;
 
+            return Ok(format!(
+                "<https://github.com/{repo_fullname}|{repo_fullname}>の条件に合う通知をこのチャンネルにも流すよ!"
+            ));
+        }
+        Args::AddTarget { path, options } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let Some(mut route) = Route::get(&dynamodb, path.clone().into_owned()).await? else {
+                return Ok(format!("ルート {path:?} はまだ登録されていないみたい。"));
+            };
+            if route.channel_id != payload.channel_id && !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                return Ok(format!(
+                    "ルート {path:?} はこのチャンネルのものじゃないから通知先を追加できないよ。"
+                ));
+            }
+
+            let mut target = ChannelTarget::new(payload.channel_id.clone());
+            for (k, v) in options {
+                apply_target_option(&mut target, k, &v)?;
+            }
+            let subscribed = route.subscribed_events();
+            // 同じチャンネルへの通知先は置き換える
+            route.targets.retain(|t| t.channel_id != target.channel_id);
+            route.targets.push(target);
+            let repo_fullname = route.repository_fullpath.clone();
+            let events = route.subscribed_events();
+            route.put(&dynamodb, path.clone().into_owned()).await?;
+
+            let msg = format!("これから<https://github.com/{repo_fullname}|{repo_fullname}>の状況をこのチャンネルにも通知していくよ!よろしくね!");
             slack::PostMessage::new(&payload.channel_id, &msg)
                 .as_user()
                 .post(&service_secrets.slack_bot_token)
                 .await?;
+
+            return Ok(
+                sync_webhook_events(service_secrets, &repo_fullname, &path, &subscribed, &events)
+                    .await
+                    .unwrap_or_default(),
+            );
+        }
+        Args::AddOverride { path, repository } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let Some(mut route) = Route::get(&dynamodb, path.clone().into_owned()).await? else {
+                return Ok(format!("ルート {path:?} はまだ登録されていないみたい。"));
+            };
+            if route.channel_id != payload.channel_id && !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                return Ok(format!(
+                    "ルート {path:?} はこのチャンネルのものじゃないから通知先を差し替えられないよ。"
+                ));
+            }
+            let Some(owner) = route.repository_fullpath.strip_suffix("/*").map(ToOwned::to_owned) else {
+                return Ok(format!(
+                    "ルート {path:?} は `オーナー/*` の形で登録されていないから、リポジトリごとの設定はできないよ。"
+                ));
+            };
+
+            route.repository_overrides.retain(|o| o.repository != repository);
+            route.repository_overrides.push(RepositoryOverride {
+                repository: repository.clone().into_owned(),
+                channel_id: payload.channel_id.clone(),
+            });
+            route.put(&dynamodb, path.into_owned()).await?;
+
+            let msg = format!("これから{owner}/{repository}の状況はこのチャンネルに通知していくよ!よろしくね!");
+            slack::PostMessage::new(&payload.channel_id, &msg)
+                .as_user()
+                .post(&service_secrets.slack_bot_token)
+                .await?;
+        }
+        Args::List { all } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let routes = if all {
+                if !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                    return Ok(String::from("すべてのルートを見られるのは管理者だけだよ。"));
+                }
+                Route::scan_all(&dynamodb).await?
+            } else {
+                Route::scan_by_channel(&dynamodb, &payload.channel_id).await?
+            };
+            if routes.is_empty() {
+                return Ok(String::from("登録されているルートはないみたい。"));
+            }
+
+            let lines = routes
+                .iter()
+                .map(|(path, r)| describe_route(path, r, all))
+                .collect::<Vec<_>>();
+            return Ok(lines.join("\n"));
+        }
+        Args::Remove { path } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let Some(route) = Route::get(&dynamodb, path.clone().into_owned()).await? else {
+                return Ok(format!("ルート {path:?} はまだ登録されていないみたい。"));
+            };
+            if route.channel_id != payload.channel_id && !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                return Ok(format!(
+                    "ルート {path:?} はこのチャンネルのものじゃないから削除できないよ。"
+                ));
+            }
+
+            // 先にWebhookを消しておけば、失敗してもやり直せる
+            let hook_note = match route.hook_id {
+                Some(id) => {
+                    github::ApiClient::new(
+                        &service_secrets.github_app_id,
+                        &service_secrets.github_app_installation_id,
+                        &service_secrets.github_app_pem,
+                        &route.repository_fullpath,
+                    )
+                    .await?
+                    .delete_webhook(id)
+                    .await?;
+                    "GitHubのWebhookも削除したよ。"
+                }
+                None => "GitHubのWebhookは手動で削除してね。",
+            };
+            Route::delete(&dynamodb, path.clone().into_owned()).await?;
+            if let Some(arn) = route.secret_arn {
+                aws_sdk_secretsmanager::Client::new(sdk_config)
+                    .delete_secret()
+                    .secret_id(arn)
+                    .force_delete_without_recovery(true)
+                    .send()
+                    .await?;
+            }
+            return Ok(format!(
+                "ルート {path:?} (<https://github.com/{0}|{0}>)を削除したよ。{hook_note}",
+                route.repository_fullpath
+            ));
+        }
+        Args::Update { path, options } => {
+            let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+            let Some(mut route) = Route::get(&dynamodb, path.clone().into_owned()).await? else {
+                return Ok(format!("ルート {path:?} はまだ登録されていないみたい。"));
+            };
+            if route.channel_id != payload.channel_id && !msq_secrets.admin_user_ids.contains(&payload.user_id) {
+                return Ok(format!(
+                    "ルート {path:?} はこのチャンネルのものじゃないから変更できないよ。"
+                ));
+            }
+
+            let subscribed = route.subscribed_events();
+            for (k, v) in options {
+                apply_route_option(&mut route, k, &v)?;
+            }
+            let mut msg = describe_route(&path, &route, false);
+            let repo_fullname = route.repository_fullpath.clone();
+            let events = route.subscribed_events();
+            route.put(&dynamodb, path.clone().into_owned()).await?;
+            if let Some(note) = sync_webhook_events(service_secrets, &repo_fullname, &path, &subscribed, &events).await
+            {
+                msg = format!("{msg}\n{note}");
+            }
+
+            return Ok(format!("ルートの設定を変えたよ!\n{msg}"));
+        }
+        Args::LinkGitHub => {
+            // 名前を自己申告させるのではなく、GitHubで本人に認可してもらう
+            let state = random_hex(32)?;
+            OAuthState::put(
+                &aws_sdk_dynamodb::Client::new(sdk_config),
+                state.clone(),
+                payload.user_id,
+            )
+            .await?;
+
+            // スラッシュコマンドの結果は実行した本人にだけ見える
+            return Ok(format!(
+                "<{}|GitHubで認可>すると、GitHubアカウントを連携できるよ!(リンクは10分間有効)",
+                github::oauth::authorize_url(&msq_secrets.github_app_client_id, &state)
+            ));
         }
     }
 
     Ok(String::new())
 }
 
-fn verify_slack_command_request<'s>(
+async fn process_pending_deployment_review(
+    sdk_config: &aws_config::SdkConfig,
+    msq_secrets: &secrets::MasqueradeConfiguratorSecrets,
+    service_secrets: &secrets::ServiceSecrets,
+    payload: SlackInteractiveMessagePayload,
+) -> Result<(), lambda_runtime::Error> {
+    let Some(action) = payload.actions.first() else {
+        return Err(ProcessError::UnknownInteraction(payload.callback_id, None).into());
+    };
+    let state = match &action.name as &str {
+        deployment_review::ACTION_APPROVE => github::DeploymentReviewState::Approved,
+        deployment_review::ACTION_REJECT => github::DeploymentReviewState::Rejected,
+        _ => return Err(ProcessError::UnknownInteraction(payload.callback_id, Some(action.name.clone())).into()),
+    };
+    let target: deployment_review::ReviewTarget = serde_json::from_str(&action.value)?;
+
+    let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+    let Some(mapping) = UserMapping::get(&dynamodb, payload.user.id.clone()).await? else {
+        slack::ResponseMessage::<slack::Attachment>::ephemeral(
+            "GitHubアカウントがまだ連携されていないみたい。`/link-repoact-github` で連携してね!",
+        )
+        .post(&payload.response_url)
+        .await?;
+        return Ok(());
+    };
+    // 承認は本人のトークンで行う(GitHub側でもレビュアーかどうかが確かめられる)
+    let Some(UserMapping {
+        github_login,
+        access_token: Some(user_token),
+        ..
+    }) = refresh_user_token(&dynamodb, msq_secrets, mapping).await?
+    else {
+        slack::ResponseMessage::<slack::Attachment>::ephemeral(
+            "GitHubアカウントの連携が切れているみたい。`/link-repoact-github` でもう一度連携してね!",
+        )
+        .post(&payload.response_url)
+        .await?;
+        return Ok(());
+    };
+
+    let apiclient = github::ApiClient::new(
+        &service_secrets.github_app_id,
+        &service_secrets.github_app_installation_id,
+        &service_secrets.github_app_pem,
+        &target.repository,
+    )
+    .await?;
+    let pending_deployments = apiclient.pending_deployments(target.run_id).await?;
+    let Some(deployment) = pending_deployments
+        .iter()
+        .find(|d| d.environment.name == target.environment)
+    else {
+        slack::ResponseMessage::<slack::Attachment>::ephemeral("このデプロイはもう承認待ちじゃないみたい。")
+            .post(&payload.response_url)
+            .await?;
+        return Ok(());
+    };
+
+    let mut is_reviewer = false;
+    for r in &deployment.reviewers {
+        is_reviewer = match r {
+            github::PendingDeploymentReviewer::User(u) => u.login.eq_ignore_ascii_case(&github_login),
+            github::PendingDeploymentReviewer::Team(t) => apiclient.is_team_member(&t.slug, &github_login).await?,
+        };
+        if is_reviewer {
+            break;
+        }
+    }
+    if !is_reviewer {
+        let msg = format!("{github_login}さんはこのデプロイのレビュアーじゃないみたい。");
+        slack::ResponseMessage::<slack::Attachment>::ephemeral(&msg)
+            .post(&payload.response_url)
+            .await?;
+        return Ok(());
+    }
+
+    let (comment, result_text, color) = match state {
+        github::DeploymentReviewState::Approved => (
+            format!("Approved by {github_login} via Slack"),
+            format!(":white_check_mark: *{github_login}さん* が承認したよ!"),
+            "#2cbe4e",
+        ),
+        github::DeploymentReviewState::Rejected => (
+            format!("Rejected by {github_login} via Slack"),
+            format!(":no_entry: *{github_login}さん* が却下したよ"),
+            "#cb2431",
+        ),
+    };
+    github::ApiClient::with_user_token(user_token, &target.repository)
+        .review_pending_deployments(target.run_id, &[deployment.environment.id], state, &comment)
+        .await?;
+
+    // ボタンを外して結果を追記する
+    let mut attachments = payload.original_message.attachments;
+    for a in &mut attachments {
+        a.remove("actions");
+        a.remove("callback_id");
+        if let Some(serde_json::Value::Array(blocks)) = a.get_mut("blocks") {
+            blocks.retain(|b| b.get("type").and_then(serde_json::Value::as_str) != Some("actions"));
+        }
+    }
+    attachments.push(serde_json::Map::from_iter([
+        (String::from("text"), serde_json::Value::from(result_text)),
+        (String::from("color"), serde_json::Value::from(color)),
+    ]));
+    slack::ResponseMessage::replace_original(&payload.original_message.text, attachments)
+        .post(&payload.response_url)
+        .await?;
+
+    Ok(())
+}
+
+/// アクセストークンの期限が切れていたら取り直す
+///
+/// 連携し直してもらう必要があればNone
+async fn refresh_user_token(
+    dynamodb: &aws_sdk_dynamodb::Client,
+    msq_secrets: &secrets::MasqueradeConfiguratorSecrets,
+    mut mapping: UserMapping,
+) -> Result<Option<UserMapping>, lambda_runtime::Error> {
+    if !mapping.token_expired() {
+        return Ok(Some(mapping));
+    }
+    let Some(ref refresh_token) = mapping.refresh_token else {
+        return Ok(None);
+    };
+
+    let token = match github::oauth::refresh_token(
+        &msq_secrets.github_app_client_id,
+        &msq_secrets.github_app_client_secret,
+        refresh_token,
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(github::oauth::OAuthError::Rejected(e)) => {
+            tracing::info!("refresh token for {} was rejected: {e}", mapping.github_login);
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    mapping.set_token(token);
+    mapping.put(dynamodb).await?;
+
+    Ok(Some(mapping))
+}
+
+/// GitHubの認可画面から戻ってきたら、認可したアカウントを連携する
+async fn process_oauth_callback(
+    sdk_config: &aws_config::SdkConfig,
+    msq_secrets: &secrets::MasqueradeConfiguratorSecrets,
+    query: OAuthCallbackQuery,
+) -> Result<String, lambda_runtime::Error> {
+    let dynamodb = aws_sdk_dynamodb::Client::new(sdk_config);
+    let Some(slack_user_id) = OAuthState::consume(&dynamodb, query.state).await? else {
+        return Ok(String::from(
+            "このリンクはもう使えないみたい。Slackで `/link-repoact-github` からやり直してね。",
+        ));
+    };
+
+    let token = github::oauth::exchange_code(
+        &msq_secrets.github_app_client_id,
+        &msq_secrets.github_app_client_secret,
+        &query.code,
+    )
+    .await?;
+    let github_login = github::oauth::authenticated_user_login(&token.access_token).await?;
+    let mut mapping = UserMapping {
+        slack_user_id,
+        github_login,
+        access_token: None,
+        refresh_token: None,
+        token_expires_at: None,
+    };
+    mapping.set_token(token);
+    mapping.put(&dynamodb).await?;
+
+    Ok(format!(
+        "GitHubアカウント {} と連携したよ!Slackに戻ってね。",
+        mapping.github_login
+    ))
+}
+
+fn apply_route_option(route: &mut Route, key: &str, value: &str) -> Result<(), ParseError> {
+    match key {
+        "workflow_runs" => {
+            route.workflow_run_notify = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "broadcast_state_changes" => {
+            route.broadcast_state_changes = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "message_format" => {
+            route.message_format = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "legacy_signature" => {
+            route.legacy_signature = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "include" => route.include_repositories = option_list(value),
+        "exclude" => route.exclude_repositories = option_list(value),
+        "events" => {
+            route.events = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        _ => return Err(ParseError::UnknownOption(key.into())),
+    }
+
+    Ok(())
+}
+
+/// 推測できないルートのパス(128bitの乱数)
+fn generate_route_id() -> Result<String, ring::error::Unspecified> {
+    random_hex(16)
+}
+
+/// 指定したバイト数の乱数を16進で
+fn random_hex(len: usize) -> Result<String, ring::error::Unspecified> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new().fill(&mut bytes)?;
+
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// ルートに対応するWebhookのURL(ベースURLが設定されていなければNone)
+fn webhook_url(path: &str) -> Option<String> {
+    let base = std::env::var("WEBHOOK_BASE_URL").ok()?;
+
+    Some(format!("{}/{path}", base.trim_end_matches('/')))
+}
+
+/// ルートのWebhookシークレットをすぐに消す(後始末なので失敗してもログに残すだけ)
+async fn delete_route_secret(secretsmanager: &aws_sdk_secretsmanager::Client, secret_id: &str) {
+    let deleted = secretsmanager
+        .delete_secret()
+        .secret_id(secret_id)
+        .force_delete_without_recovery(true)
+        .send()
+        .await;
+    if let Err(e) = deleted {
+        tracing::error!(
+            "Failed to delete route secret {secret_id}: {}",
+            aws_sdk_secretsmanager::Error::from(e)
+        );
+    }
+}
+
+/// GitHub AppとしてリポジトリもしくはOrganizationのWebhookを作成・更新する
+async fn register_webhook(
+    service_secrets: &secrets::ServiceSecrets,
+    repo_fullname: &str,
+    url: &str,
+    secret: &str,
+    events: &[&str],
+) -> Result<github::WebhookRegistration, lambda_runtime::Error> {
+    let r = github::ApiClient::new(
+        &service_secrets.github_app_id,
+        &service_secrets.github_app_installation_id,
+        &service_secrets.github_app_pem,
+        repo_fullname,
+    )
+    .await?
+    .register_webhook(url, secret, events)
+    .await?;
+
+    Ok(r)
+}
+
+/// 購読するイベントが変わっていたら、登録済みのWebhookのイベントも合わせる
+///
+/// 利用者に伝えることがあればそのメッセージを返す
+async fn sync_webhook_events(
+    service_secrets: &secrets::ServiceSecrets,
+    repo_fullname: &str,
+    path: &str,
+    previous: &[EventKind],
+    events: &[EventKind],
+) -> Option<String> {
+    if events == previous {
+        return None;
+    }
+    let Some(url) = webhook_url(path) else {
+        return Some(String::from("GitHubのWebhookで受け取るイベントも合わせて変えてね。"));
+    };
+
+    let events = events.iter().map(|k| k.as_str()).collect::<Vec<_>>();
+    let updated = async {
+        github::ApiClient::new(
+            &service_secrets.github_app_id,
+            &service_secrets.github_app_installation_id,
+            &service_secrets.github_app_pem,
+            repo_fullname,
+        )
+        .await?
+        .update_webhook_events(&url, &events)
+        .await
+        .map_err(lambda_runtime::Error::from)
+    };
+    Some(match updated.await {
+        Ok(Some(_)) => String::from("GitHubのWebhookで受け取るイベントも変えたよ!"),
+        Ok(None) => String::from("GitHubのWebhookが見つからなかったから、受け取るイベントは手動で変えてね。"),
+        Err(e) => {
+            tracing::error!("webhook events update failed for {repo_fullname}: {e}");
+            format!("GitHubのWebhookで受け取るイベントを変えられなかったみたい({e})。手動で変えてね。")
+        }
+    })
+}
+
+/// Webhookの登録方法(本人にだけ返す)
+fn webhook_setup_instructions(repo_fullname: &str, path: &str, secret: &str) -> String {
+    let url = webhook_url(path).unwrap_or_else(|| format!("(通知用APIのベースURL)/{path}"));
+    let settings_url = match repo_fullname.strip_suffix("/*") {
+        Some(owner) => format!("https://github.com/organizations/{owner}/settings/hooks/new"),
+        None => format!("https://github.com/{repo_fullname}/settings/hooks/new"),
+    };
+
+    format!(
+        "ルート `{path}` を登録したよ!<{settings_url}|Webhookの設定画面>で次のように登録してね。\n\
+        • Payload URL: `{url}`\n\
+        • Content type: `application/json`\n\
+        • Secret: `{secret}`\n\
+        • Events: 通知したいイベント(Issues, Pull requests, Discussions, Workflow runsなど)"
+    )
+}
+
+/// ルート一覧の1行
+fn describe_route(path: &str, route: &Route, with_channel: bool) -> String {
+    let mut options = vec![
+        format!("workflow_runs={}", route.workflow_run_notify.as_str()),
+        format!("broadcast_state_changes={}", route.broadcast_state_changes),
+        format!("message_format={}", route.message_format.as_str()),
+    ];
+    if !route.events.is_empty() {
+        let events = route.events.rules.iter().map(ToString::to_string).collect::<Vec<_>>();
+        options.push(format!("events={}", events.join(",")));
+    }
+    if !route.include_repositories.is_empty() {
+        options.push(format!("include={}", route.include_repositories.join(",")));
+    }
+    if !route.exclude_repositories.is_empty() {
+        options.push(format!("exclude={}", route.exclude_repositories.join(",")));
+    }
+    if route.legacy_signature {
+        options.push(String::from("legacy_signature=true"));
+    }
+    if !route.rules.is_empty() {
+        options.push(format!("rules: {}", route.rules.len()));
+    }
+    if !route.targets.is_empty() {
+        options.push(format!("targets: {}", route.targets.len()));
+    }
+
+    let channel = if with_channel {
+        format!(" → <#{}>", route.channel_id)
+    } else {
+        String::new()
+    };
+
+    format!(
+        "• `{path}`: <https://github.com/{0}|{0}>{channel} ({1})",
+        route.repository_fullpath,
+        options.join(", ")
+    )
+}
+
+fn apply_target_option(target: &mut ChannelTarget, key: &str, value: &str) -> Result<(), ParseError> {
+    match key {
+        "broadcast_state_changes" => {
+            target.broadcast_state_changes = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "message_format" => {
+            target.message_format = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        "events" => {
+            target.events = value
+                .parse()
+                .map_err(|_| ParseError::InvalidOptionValue(key.into(), value.into()))?;
+        }
+        _ => return Err(ParseError::UnknownOption(key.into())),
+    }
+
+    Ok(())
+}
+
+// カンマ区切りのオプション値
+fn option_list(value: &str) -> Vec<String> {
+    value.split(',').map(|x| String::from(x.trim())).collect()
+}
+
+fn apply_rule_option(rule: &mut RoutingRule, key: &str, value: &str) -> Result<(), ParseError> {
+    match key {
+        "labels" => rule.labels = option_list(value),
+        "base" => rule.base_branch = Some(value.into()),
+        "head" => rule.head_branch = Some(value.into()),
+        "paths" => rule.paths = option_list(value),
+        "author" => rule.author = Some(value.into()),
+        _ => return Err(ParseError::UnknownOption(key.into())),
+    }
+
+    Ok(())
+}
+
+/// これより古いSlackのリクエストは再送攻撃とみなして受け付けない
+const MAX_SLACK_REQUEST_AGE_SECS: u64 = 60 * 5;
+
+impl From<nom::Err<nom::error::Error<&str>>> for ParseError {
+    fn from(e: nom::Err<nom::error::Error<&str>>) -> Self {
+        match e {
+            // all_consumingで残った部分はそのまま見せる
+            nom::Err::Error(e) | nom::Err::Failure(e) if e.code == nom::error::ErrorKind::Eof => {
+                Self::UnexpectedInput(e.input.to_owned())
+            }
+            e => Self::SyntaxError(e.map_input(ToOwned::to_owned)),
+        }
+    }
+}
+
+fn verify_slack_command_request(
     body: &str,
     request_timestamp: &str,
     signing_secret: &str,
     expected_signature: String,
 ) -> Result<(), ProcessError> {
-    let key = hmac::Key::new(HMAC_SHA256, &signing_secret.as_bytes());
+    let timestamp: u64 = request_timestamp
+        .parse()
+        .map_err(|_| ProcessError::StaleSlackRequest(request_timestamp.into()))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    if now.abs_diff(timestamp) > MAX_SLACK_REQUEST_AGE_SECS {
+        return Err(ProcessError::StaleSlackRequest(request_timestamp.into()));
+    }
+
+    let key = hmac::Key::new(HMAC_SHA256, signing_secret.as_bytes());
     let payload = format!("v0:{request_timestamp}:{body}");
     let computed = hmac::sign(&key, payload.as_bytes());
     let mut verify_target = Vec::with_capacity(computed.as_ref().len() * 2 + 3);
     verify_target.extend(b"v0=");
-    verify_target.extend(
-        computed
-            .as_ref()
-            .into_iter()
-            .flat_map(|b| format!("{b:02x}").into_bytes()),
-    );
+    verify_target.extend(computed.as_ref().iter().flat_map(|b| format!("{b:02x}").into_bytes()));
 
     constant_time::verify_slices_are_equal(&verify_target, expected_signature.as_bytes()).map_err(|_| {
         ProcessError::SlackRequestValidationFailed(
@@ -144,18 +1127,115 @@ fn verify_slack_command_request<'s>(
 enum Args<'s> {
     Add {
         repo_fullname: Cow<'s, str>,
+        /// 省略したら推測できないものを生成する
+        path: Option<Cow<'s, str>>,
+        options: Vec<(&'s str, Cow<'s, str>)>,
+    },
+    AddRule {
+        path: Cow<'s, str>,
+        options: Vec<(&'s str, Cow<'s, str>)>,
+    },
+    AddTarget {
         path: Cow<'s, str>,
+        options: Vec<(&'s str, Cow<'s, str>)>,
     },
+    AddOverride {
+        path: Cow<'s, str>,
+        repository: Cow<'s, str>,
+    },
+    List {
+        all: bool,
+    },
+    Remove {
+        path: Cow<'s, str>,
+    },
+    Update {
+        path: Cow<'s, str>,
+        options: Vec<(&'s str, Cow<'s, str>)>,
+    },
+    LinkGitHub,
 }
 fn parse_add_args<'s>(args: &'s str) -> nom::IResult<&'s str, Args<'s>> {
-    nom::combinator::map(
+    nom::combinator::all_consuming(nom::combinator::map(
         nom::sequence::tuple((
             arg_fragment,
-            nom::bytes::complete::take_while(char::is_whitespace),
-            arg_fragment,
+            // `key=value` はパスではなくオプション
+            nom::combinator::opt(nom::sequence::preceded(
+                nom::bytes::complete::take_while1(char::is_whitespace),
+                nom::combinator::verify(arg_fragment, |s: &str| !s.contains('=')),
+            )),
+            route_options,
         )),
-        |(repo_fullname, _, path)| Args::Add { repo_fullname, path },
-    )(args)
+        |(repo_fullname, path, options)| Args::Add {
+            repo_fullname,
+            path,
+            options,
+        },
+    ))(args)
+}
+
+fn parse_add_rule_args<'s>(args: &'s str) -> nom::IResult<&'s str, Args<'s>> {
+    nom::combinator::map(nom::sequence::pair(arg_fragment, route_options), |(path, options)| {
+        Args::AddRule { path, options }
+    })(args)
+}
+
+fn parse_add_target_args<'s>(args: &'s str) -> nom::IResult<&'s str, Args<'s>> {
+    nom::combinator::map(nom::sequence::pair(arg_fragment, route_options), |(path, options)| {
+        Args::AddTarget { path, options }
+    })(args)
+}
+
+fn parse_add_override_args<'s>(args: &'s str) -> nom::IResult<&'s str, Args<'s>> {
+    nom::combinator::all_consuming(nom::combinator::map(
+        nom::sequence::separated_pair(
+            arg_fragment,
+            nom::bytes::complete::take_while1(char::is_whitespace),
+            arg_fragment,
+        ),
+        |(path, repository)| Args::AddOverride { path, repository },
+    ))(args)
+}
+
+// `/repoact-notify list [all]` / `remove path` / `update path key=value...`
+fn parse_repoact_notify_args<'s>(args: &'s str) -> nom::IResult<&'s str, Args<'s>> {
+    let ws = || nom::bytes::complete::take_while1(char::is_whitespace);
+
+    nom::combinator::all_consuming(nom::branch::alt((
+        nom::combinator::map(
+            nom::sequence::preceded(
+                nom::bytes::complete::tag("list"),
+                nom::combinator::opt(nom::sequence::preceded(ws(), nom::bytes::complete::tag("all"))),
+            ),
+            |all| Args::List { all: all.is_some() },
+        ),
+        nom::combinator::map(
+            nom::sequence::preceded(
+                nom::sequence::pair(nom::bytes::complete::tag("remove"), ws()),
+                arg_fragment,
+            ),
+            |path| Args::Remove { path },
+        ),
+        nom::combinator::map(
+            nom::sequence::preceded(
+                nom::sequence::pair(nom::bytes::complete::tag("update"), ws()),
+                nom::sequence::pair(arg_fragment, route_options),
+            ),
+            |(path, options)| Args::Update { path, options },
+        ),
+    )))(args)
+}
+
+// `key=value` 形式のオプションの並び(引数の最後まで)
+fn route_options<'s>(input: &'s str) -> nom::IResult<&'s str, Vec<(&'s str, Cow<'s, str>)>> {
+    nom::combinator::all_consuming(nom::multi::many0(nom::sequence::preceded(
+        nom::bytes::complete::take_while1(char::is_whitespace),
+        nom::sequence::separated_pair(
+            nom::bytes::complete::take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+            nom::character::complete::char('='),
+            arg_fragment,
+        ),
+    )))(input)
 }
 
 fn arg_fragment<'s>(input: &'s str) -> nom::IResult<&'s str, Cow<'s, str>> {
@@ -3,11 +3,20 @@ use futures_util::FutureExt;
 #[derive(serde::Deserialize)]
 pub struct MasqueradeConfiguratorSecrets {
     pub slack_app_signing_secret: String,
+    /// すべてのルートを一覧・変更できるSlackユーザーID
+    #[serde(default)]
+    pub admin_user_ids: Vec<String>,
+    /// GitHubアカウントの連携(ユーザー認可)に使うGitHub AppのクライアントID
+    pub github_app_client_id: String,
+    pub github_app_client_secret: String,
 }
 
 #[derive(serde::Deserialize)]
 pub struct ServiceSecrets {
     pub slack_bot_token: String,
+    pub github_app_id: String,
+    pub github_app_installation_id: String,
+    pub github_app_pem: String,
 }
 
 pub async fn load(
@@ -30,6 +30,9 @@ resource "aws_lambda_function" "function" {
   runtime          = "provided.al2023"
   architectures    = ["arm64"]
 
+  # Slack APIのレート制限で待つ時間(合計8秒まで)を含めても収まるようにしておく
+  timeout = 30
+
   environment {
     variables = {
       RUST_LOG       = var.enable_debug_log ? "trace" : "error"
@@ -102,8 +105,11 @@ resource "aws_iam_policy" "secret_read_policy" {
     Statement = [
       {
         Effect   = "Allow",
-        Action   = "secretsmanager:GetSecretValue",
-        Resource = data.aws_secretsmanager_secret.secrets.arn
+        Action = "secretsmanager:GetSecretValue",
+        Resource = [
+          data.aws_secretsmanager_secret.secrets.arn,
+          "arn:aws:secretsmanager:${data.aws_region.current.name}:${data.aws_caller_identity.current.account_id}:secret:repoact-notify/routes/*"
+        ]
       }
     ]
   })
@@ -117,13 +123,73 @@ resource "aws_iam_policy" "routemap_read_policy" {
     Statement = [
       {
         Effect   = "Allow",
-        Action   = "dynamodb:GetItem",
+        Action   = ["dynamodb:GetItem", "dynamodb:UpdateItem"],
         Resource = aws_dynamodb_table.routemap.arn
       }
     ]
   })
 }
 
+resource "aws_iam_policy" "threads_readwrite_policy" {
+  name = "${local.function_name}-LambdaThreadsReadWritePolicy"
+  path = "/webhook/masquerade/github-activity/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:GetItem", "dynamodb:PutItem"],
+        Resource = aws_dynamodb_table.threads.arn
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "deliveries_readwrite_policy" {
+  name = "${local.function_name}-LambdaDeliveriesReadWritePolicy"
+  path = "/webhook/masquerade/github-activity/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:UpdateItem"],
+        Resource = aws_dynamodb_table.deliveries.arn
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "ignored_events_write_policy" {
+  name = "${local.function_name}-LambdaIgnoredEventsWritePolicy"
+  path = "/webhook/masquerade/github-activity/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:UpdateItem"],
+        Resource = aws_dynamodb_table.ignored_events.arn
+      }
+    ]
+  })
+}
+
+resource "aws_iam_policy" "audit_log_write_policy" {
+  name = "${local.function_name}-LambdaAuditLogWritePolicy"
+  path = "/webhook/masquerade/github-activity/"
+  policy = jsonencode({
+    Version = "2012-10-17",
+    Statement = [
+      {
+        Effect   = "Allow",
+        Action   = ["dynamodb:PutItem"],
+        Resource = aws_dynamodb_table.audit_log.arn
+      }
+    ]
+  })
+}
+
 resource "aws_iam_role_policy_attachment" "execution_role_logging_policy" {
   role       = aws_iam_role.execution_role.name
   policy_arn = aws_iam_policy.logging_policy.arn
@@ -139,6 +205,26 @@ resource "aws_iam_role_policy_attachment" "execution_role_routemap_read_policy"
   policy_arn = aws_iam_policy.routemap_read_policy.arn
 }
 
+resource "aws_iam_role_policy_attachment" "execution_role_threads_readwrite_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.threads_readwrite_policy.arn
+}
+
+resource "aws_iam_role_policy_attachment" "execution_role_deliveries_readwrite_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.deliveries_readwrite_policy.arn
+}
+
+resource "aws_iam_role_policy_attachment" "execution_role_ignored_events_write_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.ignored_events_write_policy.arn
+}
+
+resource "aws_iam_role_policy_attachment" "execution_role_audit_log_write_policy" {
+  role       = aws_iam_role.execution_role.name
+  policy_arn = aws_iam_policy.audit_log_write_policy.arn
+}
+
 resource "aws_cloudwatch_log_group" "function_log_group" {
   name              = "/aws/lambda/${local.function_name}"
   retention_in_days = 1
@@ -153,10 +239,84 @@ resource "aws_dynamodb_table" "routemap" {
     name = "path"
     type = "S"
   }
+
+  attribute {
+    name = "channel_id"
+    type = "S"
+  }
+
+  global_secondary_index {
+    name            = "channel_id-index"
+    hash_key        = "channel_id"
+    projection_type = "ALL"
+  }
+}
+
+resource "aws_dynamodb_table" "threads" {
+  name         = "${local.function_name}-Threads"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "thread_key"
+
+  attribute {
+    name = "thread_key"
+    type = "S"
+  }
+}
+
+resource "aws_dynamodb_table" "deliveries" {
+  name         = "${local.function_name}-Deliveries"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "delivery_id"
+
+  attribute {
+    name = "delivery_id"
+    type = "S"
+  }
+
+  ttl {
+    attribute_name = "expires_at"
+    enabled        = true
+  }
+}
+
+resource "aws_dynamodb_table" "ignored_events" {
+  name         = "${local.function_name}-IgnoredEvents"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "event_action"
+
+  attribute {
+    name = "event_action"
+    type = "S"
+  }
+}
+
+# 拒否したWebhookの記録。調査に使うのでTTLは付けない
+resource "aws_dynamodb_table" "audit_log" {
+  name         = "${local.function_name}-AuditLog"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key     = "route_id"
+  range_key    = "rejected_at"
+
+  attribute {
+    name = "route_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "rejected_at"
+    type = "N"
+  }
+
+  point_in_time_recovery {
+    enabled = true
+  }
 }
 
 # externally defined resources
 
+data "aws_region" "current" {}
+data "aws_caller_identity" "current" {}
+
 data "aws_secretsmanager_secret" "secrets" {
   name = "repoact-notify"
 }
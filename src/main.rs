@@ -13,9 +13,22 @@ async fn main() -> Result<(), Error> {
     lambda_runtime::run(service_fn(handler)).await
 }
 
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use repoact_notify_common::{slack, Route};
+use repoact_notify_common::{
+    deployment_review, github,
+    slack::{
+        self,
+        block::{ContextElement, Element, SectionBlock, Text},
+        Block,
+    },
+    ChannelTarget, EventKind, IgnoredEventCounter, MessageThread, RejectedWebhook, Route, RoutingRule, ThreadSubject,
+    WebhookDelivery, WorkflowRunNotifyMode,
+};
 
 use crate::secrets::Secrets;
 #[derive(serde::Serialize)]
@@ -25,6 +38,46 @@ pub struct GatewayResponse {
     headers: HashMap<String, String>,
     body: String,
 }
+impl GatewayResponse {
+    fn ok() -> Self {
+        Self {
+            status_code: 200,
+            headers: HashMap::new(),
+            body: String::new(),
+        }
+    }
+
+    /// 通知せずに読み捨てたイベント
+    fn ignored() -> Self {
+        Self::json(202, serde_json::json!({ "status": "ignored" }))
+    }
+
+    fn json(status_code: usize, body: serde_json::Value) -> Self {
+        Self {
+            status_code,
+            headers: HashMap::from([(String::from("Content-Type"), String::from("application/json"))]),
+            body: body.to_string(),
+        }
+    }
+
+    /// エラーの種類をGitHubに返す
+    ///
+    /// 配信ログには誰でも見られる形で残るので、チャンネルIDや期待しているリポジトリなどの詳細はログにだけ残す。
+    /// ProcessError以外(AWSやGitHub APIの失敗など)はまとめて503にする
+    fn error(e: &(dyn std::error::Error + Send + Sync + 'static)) -> Self {
+        let (status_code, code, message) = match e.downcast_ref::<ProcessError>() {
+            Some(pe) => (pe.status_code(), pe.code(), pe.public_message()),
+            None => (503, "downstream_failure", "Downstream service call failed"),
+        };
+        if status_code >= 500 {
+            tracing::error!("Request failed ({status_code}): {e}");
+        } else {
+            tracing::warn!("Request rejected ({status_code}): {e}");
+        }
+
+        Self::json(status_code, serde_json::json!({ "error": code, "message": message }))
+    }
+}
 #[derive(serde::Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GatewayRequest {
@@ -36,7 +89,10 @@ pub struct GatewayRequest {
 #[derive(serde::Deserialize, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct GitHubWebhookHeaderValues {
-    x_hub_signature_256: String,
+    x_hub_signature_256: Option<String>,
+    x_hub_signature: Option<String>,
+    x_github_delivery: Option<String>,
+    x_github_event: Option<String>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -44,47 +100,340 @@ pub struct PathParameters {
     identifiers: String,
 }
 
-mod github;
 mod secrets;
 
 #[derive(Debug, thiserror::Error)]
 enum ProcessError {
-    #[error("Invalid Webhook signature")]
-    InvalidWebhookSignature,
+    #[error("Invalid Webhook signature: {0}")]
+    InvalidWebhookSignature(github::SignatureVerificationError),
     #[error("Webhook event parsing failed! {0}")]
     WebhookEventParsingFailed(serde_json::Error),
     #[error("Route {0:?} is not found")]
     RouteNotFound(String),
+    #[error("Repository {actual:?} is not allowed for route {route:?} (expected {expected:?})")]
+    RepositoryMismatch {
+        route: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Unhandled {} action: {}", event.as_str(), action.as_str())]
+    UnhandledAction { event: EventKind, action: github::Action },
     #[error("Field {0:?} is not contained in the payload")]
     RequireField(&'static str),
+    #[error("Slack API call failed: {0}")]
+    SlackApiFailed(slack::SlackApiError),
+    #[error("Delivery failed for {} of {total} channels: {failed:?}", failed.len())]
+    PartialDeliveryFailed {
+        /// (channel_id, error)
+        failed: Vec<(String, String)>,
+        total: usize,
+    },
+}
+impl ProcessError {
+    fn unhandled_action(event: EventKind, action: github::Action) -> Self {
+        Self::UnhandledAction { event, action }
+    }
+
+    const fn status_code(&self) -> usize {
+        match self {
+            Self::InvalidWebhookSignature(_) => 401,
+            Self::RepositoryMismatch { .. } => 403,
+            Self::RouteNotFound(_) => 404,
+            Self::WebhookEventParsingFailed(_) | Self::RequireField(_) => 400,
+            Self::UnhandledAction { .. } => 202,
+            Self::SlackApiFailed(_) | Self::PartialDeliveryFailed { .. } => 503,
+        }
+    }
+
+    const fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidWebhookSignature(_) => "invalid_signature",
+            Self::RepositoryMismatch { .. } => "repository_mismatch",
+            Self::RouteNotFound(_) => "route_not_found",
+            Self::WebhookEventParsingFailed(_) => "invalid_payload",
+            Self::RequireField(_) => "missing_field",
+            Self::UnhandledAction { .. } => "unhandled_action",
+            Self::SlackApiFailed(_) => "slack_api_failed",
+            Self::PartialDeliveryFailed { .. } => "partial_delivery_failed",
+        }
+    }
+
+    /// レスポンスに含めても問題ない説明
+    const fn public_message(&self) -> &'static str {
+        match self {
+            Self::InvalidWebhookSignature(_) => "Webhook signature verification failed",
+            Self::RepositoryMismatch { .. } => "Repository is not allowed for this route",
+            Self::RouteNotFound(_) => "Route is not found",
+            Self::WebhookEventParsingFailed(_) => "Webhook payload could not be parsed",
+            Self::RequireField(_) => "Webhook payload is missing a required field",
+            Self::UnhandledAction { .. } => "Event action is not supported",
+            Self::SlackApiFailed(_) | Self::PartialDeliveryFailed { .. } => "Slack API call failed",
+        }
+    }
 }
 
-async fn post_message(msg: slack::PostMessage<'_>, bot_token: &str) -> Result<(), Error> {
-    let resp = msg.post(bot_token).await?;
+async fn post_message(msg: slack::PostMessage<'_>, bot_token: &str) -> Result<slack::PostMessageResponse, Error> {
+    let resp = msg.post(bot_token).await.map_err(|e| {
+        tracing::error!("Post failed! channel={:?}: {e}", msg.channel);
+        ProcessError::SlackApiFailed(e)
+    })?;
     tracing::trace!("Post Successful! {resp:?}");
-    Ok(())
+    Ok(resp)
+}
+
+fn format_message<'s>(target: &ChannelTarget, msg: slack::PostMessage<'s>) -> slack::PostMessage<'s> {
+    msg.format(target.message_format)
+}
+
+/// スレッドへの投稿の種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ThreadPost {
+    /// issue/PR/Discussionが開かれたときの投稿(スレッドの親になる)
+    Root,
+    /// コメントやレビュー
+    Reply,
+    /// close/reopen/mergeなどの状態変化
+    StateChange,
 }
 
 struct ExecutionContext {
     secrets: Secrets,
     route: Route,
+    dynamodb: aws_sdk_dynamodb::Client,
+    /// 通知先(フィルタを通過したチャンネルと、マッチしたルールのチャンネル)
+    targets: Vec<ChannelTarget>,
+    /// 処理中の配信(X-GitHub-Delivery)
+    delivery_id: Option<String>,
+    /// 以前の実行で投稿済みだった送信(`delivery_key` の値)
+    delivered: HashSet<String>,
+    /// これまでに `deliver` を呼んだ回数。再送でも同じ順で呼ばれるので、送信の識別に使う
+    delivery_step: AtomicUsize,
 }
 impl ExecutionContext {
-    pub fn post_message<'s>(
+    pub async fn post_message<'s>(
         &'s self,
         msg: &'s str,
         modifier: impl FnOnce(slack::PostMessage<'s>) -> slack::PostMessage<'s>,
-    ) -> impl std::future::Future<Output = Result<(), Error>> + 's {
-        post_message(
-            modifier(slack::PostMessage::new(&self.route.channel_id, msg)),
-            &self.secrets.slack_bot_token,
-        )
+    ) -> Result<(), Error> {
+        let message = modifier(slack::PostMessage::new("", msg));
+
+        self.deliver(|t, _| {
+            let message = slack::PostMessage {
+                channel: &t.channel_id,
+                ..message.clone()
+            };
+            post_message(format_message(t, message), &self.secrets.slack_bot_token).map_ok(drop)
+        })
+        .await
+    }
+
+    /// すべての通知先に並行して送る
+    ///
+    /// 一部が失敗しても残りの送信は続けて、最後に失敗した通知先をまとめて報告する。
+    /// 以前の実行(再送前)で投稿済みだった通知先には送らない。
+    /// `send` には送信の識別子も渡すので、途中で投稿済みにしたいときは `mark_delivered` できる
+    async fn deliver<'t, F>(&'t self, send: impl Fn(&'t ChannelTarget, String) -> F) -> Result<(), Error>
+    where
+        F: std::future::Future<Output = Result<(), Error>>,
+    {
+        let step = self.delivery_step.fetch_add(1, Ordering::Relaxed);
+        let pending = self
+            .targets
+            .iter()
+            .map(|t| (t, Self::delivery_key(step, t)))
+            .filter(|(_, key)| !self.delivered.contains(key))
+            .collect::<Vec<_>>();
+        let send = &send;
+        let results = futures::future::join_all(pending.iter().map(|(t, key)| async move {
+            send(t, key.clone()).await?;
+            self.mark_delivered(key).await;
+            Ok::<_, Error>(())
+        }))
+        .await;
+        let failed = pending
+            .iter()
+            .zip(results)
+            .filter_map(|((t, _), r)| r.err().map(|e| (t.channel_id.clone(), e.to_string())))
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        for (ch, e) in &failed {
+            tracing::error!("Delivery failed! channel={ch:?}: {e}");
+        }
+        Err(ProcessError::PartialDeliveryFailed {
+            failed,
+            total: self.targets.len(),
+        }
+        .into())
+    }
+
+    fn delivery_key(step: usize, target: &ChannelTarget) -> String {
+        format!("{step}:{}", target.channel_id)
+    }
+
+    /// 送信が済んだことを記録する
+    ///
+    /// 投稿自体は済んでいるので、記録に失敗しても送信の失敗にはしない(再送時に重複するだけ)
+    async fn mark_delivered(&self, key: &str) {
+        let Some(ref id) = self.delivery_id else {
+            return;
+        };
+
+        if let Err(e) = WebhookDelivery::mark_delivered(&self.dynamodb, id, key).await {
+            tracing::warn!("Failed to record delivery {id} ({key}): {e}");
+        }
+    }
+
+    /// フィルタを通過する通知先と、ルールにマッチしたチャンネルを決める
+    ///
+    /// GitHub APIを叩く必要があるのは変更ファイルで判定するルールがあるときだけ
+    pub async fn resolve_targets(&mut self, event: &github::WebhookPayload<'_>) -> Result<(), Error> {
+        let action = event.action().map(|a| a.as_str());
+        let allows = |t: &ChannelTarget| event.kind().is_none_or(|k| t.events.allows(k, action));
+        let Some(repository) = event.repository() else {
+            self.targets.clear();
+            return Ok(());
+        };
+
+        let all_targets = self.route.all_targets(&repository.full_name);
+        let primary = all_targets[0].clone();
+        self.targets = all_targets.into_iter().filter(allows).collect();
+        if self.route.rules.is_empty() || !allows(&primary) {
+            return Ok(());
+        }
+
+        let mut subject = event.routing_subject();
+        if self.route.rules.iter().any(RoutingRule::requires_changed_paths) {
+            if let Some(number) = event.pull_request_number() {
+                let api = self.connect_github(&repository.full_name).await?;
+                subject.changed_paths = api
+                    .list_pull_request_files(number)
+                    .await?
+                    .into_iter()
+                    .map(Cow::Owned)
+                    .collect();
+            }
+        }
+
+        for r in &self.route.rules {
+            if r.matches(&subject) && !self.targets.iter().any(|t| t.channel_id == r.channel_id) {
+                // ルールのチャンネルはルート自身の設定で流す
+                self.targets.push(ChannelTarget {
+                    channel_id: r.channel_id.clone(),
+                    ..primary.clone()
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// issue/PR/Discussionごとにスレッドにまとめて投稿する
+    ///
+    /// スレッドの親がまだ記録されていなければ通常通り投稿して、それを親として記録する
+    pub async fn post_thread_message<'s>(
+        &'s self,
+        subject: ThreadSubject<'_>,
+        kind: ThreadPost,
+        msg: &'s str,
+        modifier: impl FnOnce(slack::PostMessage<'s>) -> slack::PostMessage<'s>,
+    ) -> Result<(), Error> {
+        let message = modifier(slack::PostMessage::new("", msg));
+
+        self.deliver(|t, delivery_key| {
+            let message = slack::PostMessage {
+                channel: &t.channel_id,
+                ..message.clone()
+            };
+            self.post_thread_message_to(t, delivery_key, subject, kind, format_message(t, message))
+        })
+        .await
+    }
+
+    async fn post_thread_message_to(
+        &self,
+        target: &ChannelTarget,
+        delivery_key: String,
+        subject: ThreadSubject<'_>,
+        kind: ThreadPost,
+        message: slack::PostMessage<'_>,
+    ) -> Result<(), Error> {
+        let key = subject.key(&target.channel_id);
+        let thread = if kind == ThreadPost::Root {
+            None
+        } else {
+            MessageThread::get(&self.dynamodb, key.clone()).await?
+        };
+
+        let Some(thread) = thread else {
+            let root_text = (kind == ThreadPost::Root).then(|| String::from(message.text));
+            let resp = post_message(message, &self.secrets.slack_bot_token).await?;
+            // スレッドの記録に失敗して再送されても、もう一度投稿しないようにここで投稿済みにしておく
+            self.mark_delivered(&delivery_key).await;
+            MessageThread {
+                channel_id: resp.channel,
+                ts: resp.ts,
+                root_text,
+            }
+            .put_if_absent(&self.dynamodb, key)
+            .await?;
+
+            return Ok(());
+        };
+
+        let mut message = message.thread(&thread.ts);
+        if kind == ThreadPost::StateChange && target.broadcast_state_changes {
+            message = message.reply_broadcast();
+        }
+        post_message(message, &self.secrets.slack_bot_token).await?;
+
+        Ok(())
+    }
+
+    /// issue/PR/Discussionを開いたときの通知を最新の状態に書き換える
+    pub async fn update_thread_root(
+        &self,
+        subject: ThreadSubject<'_>,
+        attachments: Vec<slack::Attachment<'_>>,
+    ) -> Result<(), Error> {
+        self.deliver(|t, _| self.update_thread_root_of(t, subject, attachments.clone()))
+            .await
+    }
+
+    async fn update_thread_root_of(
+        &self,
+        target: &ChannelTarget,
+        subject: ThreadSubject<'_>,
+        attachments: Vec<slack::Attachment<'_>>,
+    ) -> Result<(), Error> {
+        let key = subject.key(&target.channel_id);
+        let Some(MessageThread {
+            channel_id,
+            ts,
+            root_text: Some(text),
+        }) = MessageThread::get(&self.dynamodb, key).await?
+        else {
+            return Ok(());
+        };
+
+        let update = slack::UpdateMessage::new(&channel_id, &ts, &text)
+            .attachments(attachments)
+            .format(target.message_format);
+        let resp = update.post(&self.secrets.slack_bot_token).await.map_err(|e| {
+            tracing::error!("Update failed! channel={channel_id:?} ts={ts:?}: {e}");
+            ProcessError::SlackApiFailed(e)
+        })?;
+        tracing::trace!("Update Successful! {resp:?}");
+
+        Ok(())
     }
 
     pub fn connect_github<'s>(
         &'s self,
         repo_fullpath: &'s str,
-    ) -> impl std::future::Future<Output = reqwest::Result<github::ApiClient>> + 's {
+    ) -> impl std::future::Future<Output = Result<github::ApiClient<'s>, github::AppAuthError>> + 's {
         github::ApiClient::new(
             &self.secrets.github_app_id,
             &self.secrets.github_app_installation_id,
@@ -96,71 +445,336 @@ impl ExecutionContext {
 
 #[tracing::instrument]
 async fn handler(e: lambda_runtime::LambdaEvent<GatewayRequest>) -> Result<GatewayResponse, Error> {
+    // Lambdaのエラーにすると一律502になってしまうので、ステータスコードに変換して返す
+    Ok(process_request(e).await.unwrap_or_else(|e| GatewayResponse::error(&*e)))
+}
+
+async fn process_request(e: lambda_runtime::LambdaEvent<GatewayRequest>) -> Result<GatewayResponse, Error> {
     let sdk_config = aws_config::load_from_env().await;
     let secrets = Secrets::load(&sdk_config).await?;
 
-    if !github::verify_request(
-        &e.payload.body,
-        &e.payload.headers.x_hub_signature_256,
-        &secrets.github_webhook_verification_secret,
-    ) {
-        return Err(ProcessError::InvalidWebhookSignature.into());
-    }
+    // 検証に使うシークレットはルートごとに違うことがあるので、先にルートを引く
+    let dynamodb = aws_sdk_dynamodb::Client::new(&sdk_config);
+    let Some(route) = Route::get(&dynamodb, e.payload.path_parameters.identifiers.clone()).await? else {
+        return Err(ProcessError::RouteNotFound(e.payload.path_parameters.identifiers).into());
+    };
+    let active_secrets = match route.secret_arn {
+        Some(ref arn) => secrets::load_route_secrets(&sdk_config, arn).await?,
+        None => secrets.webhook_verification_secrets(),
+    };
 
-    let event: github::WebhookEvent =
-        serde_json::from_str(&e.payload.body).map_err(ProcessError::WebhookEventParsingFailed)?;
-    let Some(route) = Route::get(
-        &aws_sdk_dynamodb::Client::new(&sdk_config),
-        e.payload.path_parameters.identifiers.clone(),
+    let signature = github::WebhookSignature {
+        sha256: e.payload.headers.x_hub_signature_256.as_deref(),
+        sha1: e.payload.headers.x_hub_signature.as_deref(),
+    };
+    github::verify_request(
+        &e.payload.body,
+        &signature,
+        active_secrets.iter().map(String::as_str),
+        route.legacy_signature,
     )
-    .await?
-    else {
-        return Err(ProcessError::RouteNotFound(e.payload.path_parameters.identifiers).into());
+    .map_err(ProcessError::InvalidWebhookSignature)?;
+
+    let event_name = e.payload.headers.x_github_event.as_deref().unwrap_or_default();
+    let event =
+        github::WebhookPayload::parse(event_name, &e.payload.body).map_err(ProcessError::WebhookEventParsingFailed)?;
+    if let github::WebhookPayload::Ping(ref ping) = event {
+        return process_ping(
+            &dynamodb,
+            &secrets,
+            &e.payload.path_parameters.identifiers,
+            e.payload.headers.x_github_delivery.as_deref(),
+            &route,
+            ping,
+        )
+        .await;
+    }
+    let (Some(repository), Some(sender)) = (event.repository(), event.sender()) else {
+        // 扱っていないイベントは再送されないように受け取ったことだけ返す
+        tracing::info!("unhandled event: {event_name:?}");
+        tracing::trace!("unprocessed message: {:?}", e.payload.body);
+        count_ignored_event(&dynamodb, event_name, None).await;
+        return Ok(GatewayResponse::ignored());
     };
 
-    let ctx = ExecutionContext { secrets, route };
+    // 同じWebhookシークレットを使う別のリポジトリから他のルートへ流し込まれないようにする
+    if !route.accepts_repository(&repository.full_name) {
+        record_rejection(
+            &dynamodb,
+            RejectedWebhook {
+                route_id: &e.payload.path_parameters.identifiers,
+                expected_repository: &route.repository_fullpath,
+                actual_repository: &repository.full_name,
+                event_name,
+                delivery_id: e.payload.headers.x_github_delivery.as_deref(),
+                sender: Some(&sender.login),
+                hook_id: None,
+            },
+        )
+        .await;
+        let err = ProcessError::RepositoryMismatch {
+            route: e.payload.path_parameters.identifiers,
+            expected: route.repository_fullpath,
+            actual: repository.full_name.clone().into_owned(),
+        };
+        tracing::error!(target: "audit", sender = %sender.login, "Rejected webhook: {err}");
+        return Err(err.into());
+    }
+
+    if !route.includes_repository(&repository.full_name) {
+        tracing::info!("repository excluded by route: {}", repository.full_name);
+        return Ok(GatewayResponse::ignored());
+    }
+
+    // GitHubの再送で同じ配信が届いたら、Slackに投稿する前に落とす
+    // 前回の処理が途中で失敗していたら、まだ投稿できていない通知先にだけ送り直す
+    let delivery_id = e.payload.headers.x_github_delivery;
+    let delivered = match delivery_id {
+        Some(ref id) => match WebhookDelivery::claim(&dynamodb, id, Some(event_name)).await? {
+            Some(d) => d,
+            None => {
+                tracing::info!("duplicate delivery: {id}");
+                return Ok(GatewayResponse::ok());
+            }
+        },
+        None => HashSet::new(),
+    };
 
-    if let Some(iss) = event.issue {
-        if let Some(cm) = event.comment {
-            process_issue_comment(ctx, iss, cm, event.repository, event.sender).await?;
+    let ctx = ExecutionContext {
+        secrets,
+        route,
+        dynamodb: dynamodb.clone(),
+        targets: Vec::new(),
+        delivery_id: delivery_id.clone(),
+        delivered,
+        delivery_step: AtomicUsize::new(0),
+    };
+    let r = process_event(ctx, event).await;
+    if let Some(ref id) = delivery_id {
+        let recorded = if r.is_ok() {
+            WebhookDelivery::complete(&dynamodb, id).await
         } else {
-            process_issue_event(ctx, event.action, iss, event.repository, event.sender).await?;
+            // 失敗した配信は再送ですぐに処理し直せるように手放しておく
+            WebhookDelivery::release(&dynamodb, id).await
+        };
+        if let Err(e) = recorded {
+            tracing::error!("Failed to update delivery record {id}: {e}");
         }
-    } else if let Some(pr) = event.pull_request {
-        process_pull_request(ctx, event.action, pr, event.repository, event.sender).await?;
-    } else if let Some(d) = event.discussion {
-        if let Some(cm) = event.comment {
-            process_discussion_comment(ctx, d, cm, event.sender).await?;
-        } else {
-            process_discussion_event(ctx, event.action, d, event.repository, event.sender).await?;
+    }
+
+    r
+}
+
+async fn process_event(mut ctx: ExecutionContext, event: github::WebhookPayload<'_>) -> Result<GatewayResponse, Error> {
+    // どの通知先も受け取らないイベントはGitHub APIを叩く前に落とす
+    ctx.resolve_targets(&event).await?;
+    if ctx.targets.is_empty() {
+        tracing::info!(
+            "event filtered out by route: {:?}:{:?}",
+            event.kind().map(|k| k.as_str()),
+            event.action().map(|a| a.as_str())
+        );
+        return Ok(GatewayResponse::ignored());
+    }
+
+    let dynamodb = ctx.dynamodb.clone();
+    use github::WebhookPayload as P;
+    let r = match event {
+        P::Issues(e) => process_issue_event(ctx, e.action, e.issue, e.repository, e.sender).await,
+        P::IssueComment(e) => process_issue_comment(ctx, e.issue, e.comment, e.repository, e.sender).await,
+        P::PullRequest(e) => process_pull_request(ctx, e.action, e.pull_request, e.repository, e.sender).await,
+        P::PullRequestReview(e) => {
+            process_pull_request_review(ctx, e.action, e.pull_request, e.review, e.repository, e.sender).await
         }
-    } else if let Some(wj) = event.workflow_job {
-        process_workflow_job_events(ctx, event.action, wj, event.deployment, event.repository).await?;
-    } else {
-        tracing::trace!("unprocessed message: {:?}", e.payload.body);
+        P::PullRequestReviewComment(e) => {
+            process_pull_request_review_comment(ctx, e.action, e.pull_request, e.comment, e.repository, e.sender).await
+        }
+        P::Discussion(e) => process_discussion_event(ctx, e.action, e.discussion, e.repository, e.sender).await,
+        P::DiscussionComment(e) => {
+            process_discussion_comment(ctx, e.discussion, e.comment, e.repository, e.sender).await
+        }
+        P::WorkflowRun(e) => process_workflow_run_event(ctx, e.action, e.workflow_run, e.repository).await,
+        P::WorkflowJob(e) => {
+            process_workflow_job_events(ctx, e.action, e.workflow_job, e.deployment, e.repository).await
+        }
+        P::Release(e) => process_release_event(ctx, e.action, e.release, e.repository, e.sender).await,
+        P::Push(e) => process_push_event(ctx, e).await,
+        // pingはルートを確認した時点で処理している
+        P::Ping(_) => Ok(()),
+        P::Unknown(name) => {
+            tracing::trace!("unprocessed event: {name:?}");
+            Ok(())
+        }
+    };
+
+    match r {
+        Ok(()) => Ok(GatewayResponse::ok()),
+        Err(e) => match e.downcast_ref::<ProcessError>() {
+            // 対応していないアクションは失敗扱いにせず、読み捨てた数だけ数えておく
+            Some(ProcessError::UnhandledAction { event, action }) => {
+                tracing::info!("{e}");
+                count_ignored_event(&dynamodb, event.as_str(), Some(action.as_str())).await;
+                Ok(GatewayResponse::ignored())
+            }
+            _ => Err(e),
+        },
     }
+}
 
-    Ok(GatewayResponse {
-        status_code: 200,
-        headers: HashMap::new(),
-        body: String::new(),
-    })
+/// Webhookの設定をルートと突き合わせて、初めて届いたpingならチャンネルに接続できたことを知らせる
+async fn process_ping(
+    dynamodb: &aws_sdk_dynamodb::Client,
+    secrets: &Secrets,
+    route_id: &str,
+    delivery_id: Option<&str>,
+    route: &Route,
+    ping: &github::PingEvent<'_>,
+) -> Result<GatewayResponse, Error> {
+    let target = ping.hook_target().unwrap_or_default();
+    if !route.accepts_repository(&target) {
+        record_rejection(
+            dynamodb,
+            RejectedWebhook {
+                route_id,
+                expected_repository: &route.repository_fullpath,
+                actual_repository: &target,
+                event_name: "ping",
+                delivery_id,
+                sender: None,
+                hook_id: Some(ping.hook_id),
+            },
+        )
+        .await;
+        let err = ProcessError::RepositoryMismatch {
+            route: route_id.to_owned(),
+            expected: route.repository_fullpath.clone(),
+            actual: target.into_owned(),
+        };
+        tracing::error!(target: "audit", hook_id = ping.hook_id, "Rejected ping: {err}");
+        return Err(err.into());
+    }
+
+    // 再送やWebhookの再作成で何度も知らせないように、確認済みのWebhookは黙って受け取る
+    if route.hook_id == Some(ping.hook_id) {
+        tracing::info!("ping from already connected hook: {}", ping.hook_id);
+        return Ok(GatewayResponse::ok());
+    }
+
+    let mut warnings = Vec::new();
+    if ping.hook.config.content_type.as_deref() != Some("json") {
+        warnings.push(String::from("Content typeが `application/json` になっていないよ"));
+    }
+    if !ping.hook.active {
+        warnings.push(String::from("Webhookが無効になっているよ"));
+    }
+    let missing = route
+        .subscribed_events()
+        .into_iter()
+        .filter(|k| !ping.subscribes(*k))
+        .map(|k| format!("`{}`", k.as_str()))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        warnings.push(format!("{} のイベントが購読されていないよ", missing.join(", ")));
+    }
+
+    let events = ping
+        .hook
+        .events
+        .iter()
+        .map(|e| format!("`{e}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut msg = format!(":link: *{target}* のWebhookがつながったよ！\n購読しているイベント: {events}");
+    for w in &warnings {
+        msg.push_str(&format!("\n:warning: {w}"));
+    }
+    post_message(
+        slack::PostMessage::new(&route.channel_id, &msg).as_user(),
+        &secrets.slack_bot_token,
+    )
+    .await?;
+    Route::set_hook_id(dynamodb, route_id.to_owned(), ping.hook_id).await?;
+
+    Ok(GatewayResponse::ok())
+}
+
+/// 拒否したWebhookを監査用のテーブルに残す(残せなくてもレスポンスは変えない)
+async fn record_rejection(dynamodb: &aws_sdk_dynamodb::Client, rejection: RejectedWebhook<'_>) {
+    if let Err(e) = rejection.record(dynamodb).await {
+        tracing::error!(target: "audit", "Failed to record rejected webhook for route {:?}: {e}", rejection.route_id);
+    }
+}
+
+async fn count_ignored_event(dynamodb: &aws_sdk_dynamodb::Client, event_name: &str, action: Option<&str>) {
+    if let Err(e) = IgnoredEventCounter::increment(dynamodb, event_name, action).await {
+        tracing::warn!("Failed to count ignored event {event_name:?}:{action:?}: {e}");
+    }
+}
+
+const COLOR_OPEN: &str = "#6cc644";
+const COLOR_CLOSED: &str = "#bd2c00";
+const COLOR_DRAFT_PR: &str = "#6c737c";
+const COLOR_OPEN_PR: &str = "#4078c0";
+const COLOR_MERGED_PR: &str = "#6e5494";
+const COLOR_REVIEW_APPROVED: &str = "#2cbe4e";
+const COLOR_REVIEW_CHANGES_REQUESTED: &str = "#cb2431";
+const COLOR_REVIEW_COMMENTED: &str = "#6a737d";
+const COLOR_RELEASE: &str = "#2cbe4e";
+const COLOR_PRERELEASE: &str = "#dbab09";
+const COLOR_WORKFLOW_SUCCESS: &str = "#2cbe4e";
+const COLOR_WORKFLOW_FAILURE: &str = "#cb2431";
+const COLOR_WORKFLOW_NEUTRAL: &str = "#6a737d";
+const COLOR_PUSH: &str = "#4078c0";
+const COLOR_FORCE_PUSH: &str = "#e36209";
+
+// Block Kitで表示する通知先向けの部品(色はattachmentのものが使われる)
+
+/// 投稿者のアイコンと名前
+fn author_context<'s>(login: &'s str, html_url: &str, avatar_url: &'s str) -> Block<'s> {
+    Block::context(vec![
+        ContextElement::Element(Element::image(avatar_url, login)),
+        ContextElement::Text(Text::mrkdwn(format!("<{html_url}|{login}>"))),
+    ])
+}
+
+/// sectionのfieldsに並べる項目
+fn block_field<'s>(title: &str, value: &str) -> Text<'s> {
+    Text::mrkdwn(format!("*{title}*\n{value}"))
 }
 
-#[derive(Debug)]
-struct UnhandledDiscussionActionError(github::Action);
-impl std::error::Error for UnhandledDiscussionActionError {}
-impl std::fmt::Display for UnhandledDiscussionActionError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "Unhandled Discussion Action: {:?}", self.0)
+/// issue/PR/Discussionの本体(リンク付きのタイトルと本文に、作成者のアイコンを添える)
+fn item_blocks<'s>(
+    user: &'s github::User,
+    title: &str,
+    url: &str,
+    body: &str,
+    fields: Vec<Text<'s>>,
+) -> Vec<Block<'s>> {
+    let text = if body.is_empty() {
+        format!("*<{url}|{title}>*")
+    } else {
+        format!("*<{url}|{title}>*\n{body}")
+    };
+    let mut blocks = vec![SectionBlock::mrkdwn(text)
+        .accessory(Element::image(&user.avatar_url, &user.login))
+        .into()];
+    if !fields.is_empty() {
+        blocks.push(SectionBlock::fields_only(fields).into());
     }
+    blocks.push(author_context(&user.login, &user.html_url, &user.avatar_url));
+
+    blocks
 }
 
-const COLOR_OPEN: &'static str = "#6cc644";
-const COLOR_CLOSED: &'static str = "#bd2c00";
-const COLOR_DRAFT_PR: &'static str = "#6c737c";
-const COLOR_OPEN_PR: &'static str = "#4078c0";
-const COLOR_MERGED_PR: &'static str = "#6e5494";
+/// コメントやレビューの本文
+fn comment_blocks<'s>(user: &'s github::User, body: &str) -> Vec<Block<'s>> {
+    let mut blocks = vec![author_context(&user.login, &user.html_url, &user.avatar_url)];
+    if !body.is_empty() {
+        blocks.push(Block::section(body.to_owned()));
+    }
+
+    blocks
+}
 
 async fn process_discussion_event<'s>(
     ctx: ExecutionContext,
@@ -173,22 +787,34 @@ async fn process_discussion_event<'s>(
         github::Action::Created => format!("*{}さん* がDiscussionを開いたよ！", sender.login),
         github::Action::Closed => format!("*{}さん* がDiscussionを閉じたよ", sender.login),
         github::Action::Reopened => format!("*{}さん* がDiscussionを再開したよ", sender.login),
-        _ => return Err(UnhandledDiscussionActionError(action).into()),
+        _ => return Err(ProcessError::unhandled_action(EventKind::Discussion, action).into()),
     };
     let a_title = format!("[{}]#{}: {}", repo.full_name, d.number, d.title);
 
-    let main_attachment = slack::Attachment::new(d.body.as_deref().unwrap_or(""))
+    let body = d.body.as_deref().unwrap_or("");
+    let main_attachment = slack::Attachment::new(body)
         .author(&d.user.login, &d.user.html_url, &d.user.avatar_url)
         .title(&a_title, &d.html_url)
-        .color(if d.is_closed() { COLOR_CLOSED } else { COLOR_OPEN });
+        .color(if d.is_closed() { COLOR_CLOSED } else { COLOR_OPEN })
+        .blocks(item_blocks(&d.user, &a_title, &d.html_url, body, Vec::new()));
 
-    ctx.post_message(&msg, |x| x.as_user().attachments(vec![main_attachment]))
+    let subject = ThreadSubject::Discussion {
+        repository: &repo.full_name,
+        number: d.number,
+    };
+    let kind = if action == github::Action::Created {
+        ThreadPost::Root
+    } else {
+        ThreadPost::StateChange
+    };
+    ctx.post_thread_message(subject, kind, &msg, |x| x.as_user().attachments(vec![main_attachment]))
         .await
 }
 async fn process_discussion_comment<'s>(
     ctx: ExecutionContext,
     d: github::Discussion<'s>,
     cm: github::Comment<'s>,
+    repo: github::Repository<'s>,
     sender: github::User<'s>,
 ) -> Result<(), Error> {
     let tail_char = format!(
@@ -220,19 +846,17 @@ async fn process_discussion_comment<'s>(
 
     let attachment = slack::Attachment::new(&cm.body)
         .author(&sender.login, &sender.html_url, &sender.avatar_url)
-        .color(color);
+        .color(color)
+        .blocks(comment_blocks(&sender, &cm.body));
 
-    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
-        .await
-}
-
-#[derive(Debug)]
-pub struct UnhandledIssueActionError(github::Action);
-impl std::error::Error for UnhandledIssueActionError {}
-impl std::fmt::Display for UnhandledIssueActionError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "Unhandled issue action: {:?}", self.0)
-    }
+    let subject = ThreadSubject::Discussion {
+        repository: &repo.full_name,
+        number: d.number,
+    };
+    ctx.post_thread_message(subject, ThreadPost::Reply, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await
 }
 
 async fn process_issue_event<'s>(
@@ -242,33 +866,76 @@ async fn process_issue_event<'s>(
     repo: github::Repository<'s>,
     sender: github::User<'s>,
 ) -> Result<(), Error> {
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: iss.number,
+    };
+    if action.is_silent_update() {
+        // ラベルや本文の変更はスレッドに流さず、親の通知だけ書き換える
+        let issue_att_title = issue_attachment_title(&iss, &repo);
+        return ctx
+            .update_thread_root(subject, vec![issue_attachment(&iss, &issue_att_title)])
+            .await;
+    }
+
     let msg = match action {
         github::Action::Opened => format!(":issue-o: *{}さん* がissueを立てたよ！ :issue-o:", sender.login),
         github::Action::Closed => format!(":issue-c: *{}さん* がissueを閉じたよ :issue-c:", sender.login),
         github::Action::Reopened => format!(":issue-o: *{}さん* がissueをもう一回開いたよ :issue-o:", sender.login),
-        _ => return Err(UnhandledIssueActionError(action).into()),
+        _ => return Err(ProcessError::unhandled_action(EventKind::Issues, action).into()),
     };
-    let issue_att_title = format!("[{}]#{}: {}", repo.full_name, iss.number, iss.title);
+    let issue_att_title = issue_attachment_title(&iss, &repo);
+    let attachment = issue_attachment(&iss, &issue_att_title);
+
+    if action == github::Action::Opened {
+        return ctx
+            .post_thread_message(subject, ThreadPost::Root, &msg, |x| {
+                x.as_user().attachments(vec![attachment])
+            })
+            .await;
+    }
 
+    ctx.post_thread_message(subject, ThreadPost::StateChange, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await?;
+    ctx.update_thread_root(subject, vec![issue_attachment(&iss, &issue_att_title)])
+        .await
+}
+
+fn issue_attachment_title(iss: &github::Issue, repo: &github::Repository) -> String {
+    format!(
+        "{} [{}]#{}: {}",
+        if iss.is_closed() { ":issue-c:" } else { ":issue-o:" },
+        repo.full_name,
+        iss.number,
+        iss.title
+    )
+}
+
+// 最初の通知とその後の書き換えとで共通のissueのattachment
+fn issue_attachment<'s>(iss: &'s github::Issue, title: &'s str) -> slack::Attachment<'s> {
     let mut att_fields = Vec::with_capacity(1);
+    let mut block_fields = Vec::with_capacity(1);
     if !iss.labels.is_empty() {
         let mut label_texts = iss.labels.iter().map(|l| l.name).collect::<Vec<_>>();
         label_texts.sort();
 
+        block_fields.push(block_field("Labelled", &label_texts.join(",")));
         att_fields.push(slack::AttachmentField {
             title: "Labelled",
             short: false,
             value: label_texts.join(","),
         });
     }
-    let attachment = slack::Attachment::new(iss.body.as_deref().unwrap_or(""))
+
+    let body = iss.body.as_deref().unwrap_or("");
+    slack::Attachment::new(body)
         .author(&iss.user.login, &iss.user.html_url, &iss.user.avatar_url)
-        .title(&issue_att_title, &iss.html_url)
+        .title(title, &iss.html_url)
         .color(if iss.is_closed() { COLOR_CLOSED } else { COLOR_OPEN })
-        .fields(att_fields);
-
-    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
-        .await
+        .fields(att_fields)
+        .blocks(item_blocks(&iss.user, title, &iss.html_url, body, block_fields))
 }
 
 async fn process_issue_comment<'s>(
@@ -330,19 +997,25 @@ async fn process_issue_comment<'s>(
 
     let attachment = slack::Attachment::new(&cm.body)
         .author(&sender.login, &sender.html_url, &sender.avatar_url)
-        .color(color);
-
-    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
-        .await
-}
+        .color(color)
+        .blocks(comment_blocks(&sender, &cm.body));
 
-#[derive(Debug)]
-pub struct UnhandledPullRequestActionError(github::Action);
-impl std::error::Error for UnhandledPullRequestActionError {}
-impl std::fmt::Display for UnhandledPullRequestActionError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "Unhandled pull request action: {:?}", self.0)
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: iss.number,
+    };
+    ctx.post_thread_message(subject, ThreadPost::Reply, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await?;
+    if iss.is_pr() {
+        // PRの状態はissueとしての情報からはわからないので書き換えない
+        return Ok(());
     }
+
+    let issue_att_title = issue_attachment_title(&iss, &repo);
+    ctx.update_thread_root(subject, vec![issue_attachment(&iss, &issue_att_title)])
+        .await
 }
 
 async fn process_pull_request<'s>(
@@ -352,17 +1025,13 @@ async fn process_pull_request<'s>(
     repo: github::Repository<'s>,
     sender: github::User<'s>,
 ) -> Result<(), Error> {
-    let merged = match pr.merged {
-        Some(m) => m,
-        None => {
-            ctx.connect_github(&repo.full_name)
-                .await?
-                .query_pullrequest_flags(pr.number)
-                .await?
-                .merged
-        }
-    };
-    let msg_base = match (action, merged, pr.draft) {
+    let merged = pull_request_merged(&ctx, &pr, &repo).await?;
+    if action.is_silent_update() {
+        // ラベルや本文の変更はスレッドに流さず、親の通知だけ書き換える
+        return update_pull_request_root(&ctx, &pr, merged, &repo).await;
+    }
+
+    let msg_base = match (&action, merged, pr.draft) {
         (github::Action::ReadyForReview, _, _) => format!(
             ":pr: *{}さん* の <{}|:pr-draft:#{}: {}> がレビューできるようになったよ！よろしくね！ :pr:",
             sender.login, pr.html_url, pr.number, pr.title
@@ -383,9 +1052,9 @@ async fn process_pull_request<'s>(
             format!(":merge: *{}さん* がPullRequestをマージしたよ！ :merge:", sender.login)
         }
         (github::Action::Closed, false, _) => format!("*{}さん* がPullRequestを閉じたよ", sender.login),
-        _ => return Err(UnhandledPullRequestActionError(action).into()),
+        _ => return Err(ProcessError::unhandled_action(EventKind::PullRequest, action).into()),
     };
-    let att_title = format!("[{}]#{}: {}", repo.full_name, pr.number, pr.title);
+    let att_title = pull_request_attachment_title(&pr, merged, &repo);
     let draft_msg = if pr.draft && action == github::Action::Opened {
         [
             "\nこのPRはまだドラフト状態だよ！",
@@ -400,9 +1069,78 @@ async fn process_pull_request<'s>(
     };
     let msg = format!("{msg_base}{draft_msg}");
 
+    let attachment = pull_request_attachment(&pr, merged, &att_title);
+
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: pr.number,
+    };
+    if action == github::Action::Opened {
+        return ctx
+            .post_thread_message(subject, ThreadPost::Root, &msg, |x| {
+                x.as_user().attachments(vec![attachment])
+            })
+            .await;
+    }
+
+    ctx.post_thread_message(subject, ThreadPost::StateChange, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await?;
+    ctx.update_thread_root(subject, vec![pull_request_attachment(&pr, merged, &att_title)])
+        .await
+}
+
+/// PRがマージ済みか(ペイロードに含まれていなければAPIで調べる)
+async fn pull_request_merged(
+    ctx: &ExecutionContext,
+    pr: &github::PullRequest<'_>,
+    repo: &github::Repository<'_>,
+) -> Result<bool, Error> {
+    match pr.merged {
+        Some(m) => Ok(m),
+        None => Ok(ctx
+            .connect_github(&repo.full_name)
+            .await?
+            .query_pullrequest_flags(pr.number)
+            .await?
+            .merged),
+    }
+}
+
+/// PRのスレッドの親の通知を最新の状態に書き換える
+async fn update_pull_request_root(
+    ctx: &ExecutionContext,
+    pr: &github::PullRequest<'_>,
+    merged: bool,
+    repo: &github::Repository<'_>,
+) -> Result<(), Error> {
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: pr.number,
+    };
+    let att_title = pull_request_attachment_title(pr, merged, repo);
+
+    ctx.update_thread_root(subject, vec![pull_request_attachment(pr, merged, &att_title)])
+        .await
+}
+
+fn pull_request_attachment_title(pr: &github::PullRequest, merged: bool, repo: &github::Repository) -> String {
+    let icon = match (pr.is_closed(), merged, pr.draft) {
+        (true, true, _) => ":merge:",
+        (true, false, _) => ":pr-closed:",
+        (false, _, true) => ":pr-draft:",
+        (false, _, false) => ":pr:",
+    };
+
+    format!("{icon} [{}]#{}: {}", repo.full_name, pr.number, pr.title)
+}
+
+// 最初の通知とその後の書き換えとで共通のPRのattachment
+fn pull_request_attachment<'s>(pr: &'s github::PullRequest, merged: bool, title: &'s str) -> slack::Attachment<'s> {
     let branch_flow_name = detect_branch_flow(
-        pr.head.label.splitn(2, ":").nth(1).unwrap_or(&pr.head.label),
-        pr.base.label.splitn(2, ":").nth(1).unwrap_or(&pr.base.label),
+        pr.head.label.split_once(':').map_or(&pr.head.label as &str, |(_, b)| b),
+        pr.base.label.split_once(':').map_or(&pr.base.label as &str, |(_, b)| b),
     );
     let mut att_fields = vec![slack::AttachmentField {
         title: "Branch Flow",
@@ -419,29 +1157,170 @@ async fn process_pull_request<'s>(
             value: label_texts.join(","),
         });
     }
+    att_fields.push(slack::AttachmentField {
+        title: "State",
+        short: true,
+        value: String::from(match (pr.is_closed(), merged, pr.draft) {
+            (true, true, _) => "Merged",
+            (true, false, _) => "Closed",
+            (false, _, true) => "Draft",
+            (false, _, false) => "Open",
+        }),
+    });
+    if let Some(u) = pr.merged_by.as_ref().filter(|_| merged) {
+        att_fields.push(slack::AttachmentField {
+            title: "Merged by",
+            short: true,
+            value: format!("<{}|{}>", u.html_url, u.login),
+        });
+    }
 
-    let attachment = slack::Attachment::new(pr.body.as_deref().unwrap_or(""))
+    let block_fields = att_fields.iter().map(|f| block_field(f.title, &f.value)).collect();
+    let body = pr.body.as_deref().unwrap_or("");
+    slack::Attachment::new(body)
         .author(&pr.user.login, &pr.user.html_url, &pr.user.avatar_url)
-        .title(&att_title, &pr.html_url)
+        .title(title, &pr.html_url)
+        .blocks(item_blocks(&pr.user, title, &pr.html_url, body, block_fields))
         .fields(att_fields)
-        .color(match (action, merged, pr.draft) {
-            (github::Action::Closed, true, _) => COLOR_MERGED_PR, // merged pr
-            (github::Action::Closed, false, _) => COLOR_CLOSED,   // unmerged but closed pr
-            (_, _, true) => COLOR_DRAFT_PR,                       // draft pr
-            _ => COLOR_OPEN_PR,                                   // opened pr
-        });
+        .color(match (pr.is_closed(), merged, pr.draft) {
+            (true, true, _) => COLOR_MERGED_PR, // merged pr
+            (true, false, _) => COLOR_CLOSED,   // unmerged but closed pr
+            (false, _, true) => COLOR_DRAFT_PR, // draft pr
+            (false, _, false) => COLOR_OPEN_PR, // opened pr
+        })
+}
 
-    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
-        .await
+async fn process_pull_request_review<'s>(
+    ctx: ExecutionContext,
+    action: github::Action,
+    pr: github::PullRequest<'s>,
+    rv: github::Review<'s>,
+    repo: github::Repository<'s>,
+    sender: github::User<'s>,
+) -> Result<(), Error> {
+    let pr_icon = if pr.draft { ":pr-draft:" } else { ":pr:" };
+    let pr_link = format!("<{}|{pr_icon}#{}({})>", pr.html_url, pr.number, pr.title);
+    let (msg, color) = match (&action, rv.state) {
+        (github::Action::Dismissed, _) => (
+            format!("*{}さん* が {pr_link} へのレビューを取り下げたよ", sender.login),
+            COLOR_DRAFT_PR,
+        ),
+        (github::Action::Submitted, github::ReviewState::Approved) => (
+            format!(
+                ":white_check_mark: *{}さん* が {pr_link} を<{}|承認>したよ！",
+                sender.login, rv.html_url
+            ),
+            COLOR_REVIEW_APPROVED,
+        ),
+        (github::Action::Submitted, github::ReviewState::ChangesRequested) => (
+            format!(
+                ":warning: *{}さん* が {pr_link} に<{}|変更をリクエスト>したよ",
+                sender.login, rv.html_url
+            ),
+            COLOR_REVIEW_CHANGES_REQUESTED,
+        ),
+        (github::Action::Submitted, github::ReviewState::Commented) => {
+            if rv.body.as_deref().is_none_or(str::is_empty) {
+                // 本文なしのレビューはレビューコメント単体の通知で十分なので流さない
+                let merged = pull_request_merged(&ctx, &pr, &repo).await?;
+                return update_pull_request_root(&ctx, &pr, merged, &repo).await;
+            }
+
+            (
+                format!("*{}さん* が {pr_link} を<{}|レビュー>したよ", sender.login, rv.html_url),
+                COLOR_REVIEW_COMMENTED,
+            )
+        }
+        _ => return Err(ProcessError::unhandled_action(EventKind::PullRequestReview, action).into()),
+    };
+    let att_title = format!("[{}]#{}: {}", repo.full_name, pr.number, pr.title);
+
+    let body = rv.body.as_deref().unwrap_or("");
+    let mut blocks = vec![Block::section(format!("*<{}|{att_title}>*", pr.html_url))];
+    blocks.extend(comment_blocks(&rv.user, body));
+    let attachment = slack::Attachment::new(body)
+        .author(&rv.user.login, &rv.user.html_url, &rv.user.avatar_url)
+        .title(&att_title, &pr.html_url)
+        .color(color)
+        .blocks(blocks);
+
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: pr.number,
+    };
+    ctx.post_thread_message(subject, ThreadPost::Reply, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await?;
+    // レビューの間にタイトルやラベル、ドラフト状態が変わっていることがあるので親も書き換える
+    let merged = pull_request_merged(&ctx, &pr, &repo).await?;
+    update_pull_request_root(&ctx, &pr, merged, &repo).await
 }
 
-#[derive(Debug)]
-pub struct UnhandledWorkflowJobActionError(github::Action);
-impl std::error::Error for UnhandledWorkflowJobActionError {}
-impl std::fmt::Display for UnhandledWorkflowJobActionError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "Unhandled workflow_job action: {:?}", self.0)
+// レビューコメントに添える差分の行数(コメント対象行に近い末尾から)
+const REVIEW_COMMENT_DIFF_HUNK_LINES: usize = 6;
+
+async fn process_pull_request_review_comment<'s>(
+    ctx: ExecutionContext,
+    action: github::Action,
+    pr: github::PullRequest<'s>,
+    cm: github::Comment<'s>,
+    repo: github::Repository<'s>,
+    sender: github::User<'s>,
+) -> Result<(), Error> {
+    if action != github::Action::Created {
+        return Err(ProcessError::unhandled_action(EventKind::PullRequestReviewComment, action).into());
     }
+
+    let pr_icon = if pr.draft { ":pr-draft:" } else { ":pr:" };
+    let path = cm.path.as_deref().ok_or(ProcessError::RequireField("comment.path"))?;
+    let msg = format!(
+        "*{}さん* が <{}|{pr_icon}#{}({})> の `{path}` に<{}|レビューコメント>したよ",
+        sender.login, pr.html_url, pr.number, pr.title, cm.html_url
+    );
+
+    let location = match cm.line_range() {
+        Some((s, e)) if s != e => format!("`{path}` L{s}-L{e}"),
+        Some((_, e)) => format!("`{path}` L{e}"),
+        None => format!("`{path}`"),
+    };
+    let mut blocks = comment_blocks(&sender, &cm.body);
+    blocks.push(Block::context(vec![ContextElement::Text(Text::mrkdwn(
+        location.clone(),
+    ))]));
+    let mut att_fields = Vec::with_capacity(2);
+    att_fields.push(slack::AttachmentField {
+        title: "File",
+        value: location,
+        short: false,
+    });
+    if let Some(ref hunk) = cm.diff_hunk {
+        let lines = hunk.lines().collect::<Vec<_>>();
+        let excerpt = &lines[lines.len().saturating_sub(REVIEW_COMMENT_DIFF_HUNK_LINES)..];
+        let diff = format!("```\n{}\n```", excerpt.join("\n"));
+
+        blocks.push(Block::section(diff.clone()));
+        att_fields.push(slack::AttachmentField {
+            title: "Diff",
+            value: diff,
+            short: false,
+        });
+    }
+
+    let attachment = slack::Attachment::new(&cm.body)
+        .author(&sender.login, &sender.html_url, &sender.avatar_url)
+        .color(if pr.draft { COLOR_DRAFT_PR } else { COLOR_OPEN_PR })
+        .fields(att_fields)
+        .blocks(blocks);
+
+    let subject = ThreadSubject::Issue {
+        repository: &repo.full_name,
+        number: pr.number,
+    };
+    ctx.post_thread_message(subject, ThreadPost::Reply, &msg, |x| {
+        x.as_user().attachments(vec![attachment])
+    })
+    .await
 }
 
 async fn process_workflow_job_events(
@@ -461,57 +1340,76 @@ async fn process_workflow_job_events(
             pub commit: github::graphql::Commit,
         }
 
-        let (run_details, init_captures) = futures::try_join!(job.run_details().map_err(Into::into), async {
+        let (run_details, (reviewers, commit)) = futures::try_join!(job.run_details().map_err(Into::into), async {
             let apiclient = ctx.connect_github(&repository.full_name).await?;
-            apiclient
+            let init_captures = apiclient
                 .post_graphql::<github::graphql::QueryResponse<InitCapture>>(&format!(
                     "query {{ {reviewers}, commit: {commit} }}",
-                    reviewers = apiclient.environment_protection_rule_query(&deployment.environment, None),
-                    commit = apiclient.commit_message_and_committer_name_query(&job.head_sha)
+                    reviewers = apiclient.environment_protection_rule_query(deployment.environment, None, None),
+                    commit = apiclient.commit_message_and_committer_name_query(job.head_sha)
                 ))
                 .await?
-                .data()
-                .map_err(Error::from)
+                .data()?;
+            let reviewers = apiclient
+                .collect_environment_reviewers(deployment.environment, init_captures.repository)
+                .await?;
+
+            Ok::<_, Error>((reviewers, init_captures.commit))
         })?;
 
-        let reviewer_users = init_captures
-            .repository
-            .environment
-            .protection_rules
-            .nodes
-            .into_iter()
-            .flat_map(|r| r.reviewers.nodes)
-            .filter_map(|r| match r {
-                github::graphql::DeploymentReviewer::User { login, .. } => Some(login),
-                // TODO: Team?
-            })
-            .collect::<Vec<_>>();
+        let mut mentioned_users = HashSet::new();
+        let mut reviewer_mentions = Vec::new();
+        for r in reviewers {
+            match r {
+                github::graphql::DeploymentReviewer::User { login } => {
+                    if mentioned_users.insert(login.clone()) {
+                        reviewer_mentions.push(format!("{login}さん"));
+                    }
+                }
+                github::graphql::DeploymentReviewer::Team {
+                    name,
+                    combined_slug,
+                    members,
+                    ..
+                } => {
+                    if members.nodes.is_empty() {
+                        // メンバーが見えない(権限がない)ときはチームそのものに呼びかける
+                        reviewer_mentions.push(format!("{name}チーム({combined_slug})のみなさん"));
+                        continue;
+                    }
+
+                    let member_names = members
+                        .nodes
+                        .into_iter()
+                        .map(|m| m.login)
+                        .filter(|l| mentioned_users.insert(l.clone()))
+                        .collect::<Vec<_>>();
+                    if !member_names.is_empty() {
+                        reviewer_mentions.push(format!("{combined_slug}チーム({})のみなさん", member_names.join("、")));
+                    }
+                }
+            }
+        }
         let prefix = [
             "以下のデプロイが承認待ちだよ!",
             "以下のデプロイをすすめるには承認が必要みたい。",
         ]
         .choose(&mut rand::thread_rng())
         .unwrap();
-        let msg = format!(
-            "{prefix}\n{} よろしくね!",
-            reviewer_users
-                .into_iter()
-                .map(|gh| format!("{gh}さん"))
-                .collect::<Vec<_>>()
-                .join("、")
-        );
+        let msg = format!("{prefix}\n{} よろしくね!", reviewer_mentions.join("、"));
 
+        let commit_info = format!(
+            "<{commit_url}|ブランチ {} のコミット {}> (コミッターさん: {})「{}」",
+            job.head_branch,
+            &job.head_sha[..8],
+            commit.committer.name,
+            commit.message,
+            commit_url = github::commit_html_url(&repository, job.head_sha),
+        );
         let att_fields = vec![
             slack::AttachmentField {
                 title: "コミット情報",
-                value: format!(
-                    "<{commit_url}|ブランチ {} のコミット {}> (コミッターさん: {})「{}」",
-                    job.head_branch,
-                    &job.head_sha[..8],
-                    init_captures.commit.committer.name,
-                    init_captures.commit.message,
-                    commit_url = github::commit_html_url(&repository, &job.head_sha),
-                ),
+                value: commit_info.clone(),
                 short: false,
             },
             slack::AttachmentField {
@@ -530,14 +1428,441 @@ async fn process_workflow_job_events(
             "[{}] {} #{}",
             repository.full_name, job.workflow_name, run_details.run_number
         );
-        let attachment = slack::Attachment::new("").title(&title, &url).fields(att_fields);
+        let review_target = serde_json::to_string(&deployment_review::ReviewTarget {
+            repository: repository.full_name.clone().into_owned(),
+            run_id: job.run_id,
+            environment: String::from(deployment.environment),
+        })?;
+        let blocks = vec![
+            SectionBlock::mrkdwn(format!("*<{url}|{title}>*"))
+                .accessory(Element::link_button("ワークフローを見る", "open_workflow_run", &url))
+                .into(),
+            SectionBlock::fields_only(vec![
+                block_field("Environment", deployment.environment),
+                block_field("ジョブ名", job.name),
+            ])
+            .into(),
+            Block::section(format!("*コミット情報*\n{commit_info}")),
+            // callback_idはblock_idとして渡す
+            Block::actions(
+                Some(deployment_review::CALLBACK_ID),
+                vec![
+                    Element::button("承認する", deployment_review::ACTION_APPROVE, review_target.clone()).primary(),
+                    Element::button("却下する", deployment_review::ACTION_REJECT, review_target.clone()).danger(),
+                ],
+            ),
+        ];
+        let attachment = slack::Attachment::new("")
+            .title(&title, &url)
+            .fields(att_fields)
+            .actions(
+                deployment_review::CALLBACK_ID,
+                vec![
+                    slack::AttachmentAction::button(
+                        deployment_review::ACTION_APPROVE,
+                        "承認する",
+                        review_target.clone(),
+                    )
+                    .primary(),
+                    slack::AttachmentAction::button(deployment_review::ACTION_REJECT, "却下する", review_target)
+                        .danger(),
+                ],
+            )
+            .blocks(blocks);
 
         ctx.post_message(&msg, |p| p.as_user().attachments(vec![attachment]))
             .await?;
         return Ok(());
     }
 
-    Err(UnhandledWorkflowJobActionError(action).into())
+    Err(ProcessError::unhandled_action(EventKind::WorkflowJob, action).into())
+}
+
+// リリースノートはこの文字数で切り詰める
+const RELEASE_NOTE_MAX_CHARS: usize = 1500;
+
+async fn process_release_event(
+    ctx: ExecutionContext,
+    action: github::Action,
+    rel: github::Release<'_>,
+    repo: github::Repository<'_>,
+    sender: github::User<'_>,
+) -> Result<(), Error> {
+    let msg = match action {
+        github::Action::Published if rel.prerelease => format!(
+            ":package: *{}さん* がプレリリース `{}` を公開したよ！ :package:",
+            sender.login, rel.tag_name
+        ),
+        github::Action::Published => format!(
+            ":tada: *{}さん* がリリース `{}` を公開したよ！ :tada:",
+            sender.login, rel.tag_name
+        ),
+        // プレリリースを公開するとpublishedも一緒に届くので、そちらで通知する
+        github::Action::Prereleased => return Ok(()),
+        github::Action::Edited => format!("*{}さん* がリリース `{}` を編集したよ", sender.login, rel.tag_name),
+        _ => return Err(ProcessError::unhandled_action(EventKind::Release, action).into()),
+    };
+    let title = format!(
+        "[{}] {}",
+        repo.full_name,
+        rel.name.as_deref().filter(|n| !n.is_empty()).unwrap_or(&rel.tag_name)
+    );
+    // 変換後に切るとmrkdwnのリンクが途中で切れて崩れるので、元のmarkdownを切ってから変換する
+    let notes = markdown_to_mrkdwn(&truncate_text(
+        rel.body.as_deref().unwrap_or(""),
+        RELEASE_NOTE_MAX_CHARS,
+    ));
+
+    let tag = format!("`{}`", rel.tag_name);
+    let prerelease = if rel.prerelease { "Yes" } else { "No" };
+    let mut blocks = vec![
+        Block::header(title.clone()),
+        author_context(&rel.author.login, &rel.author.html_url, &rel.author.avatar_url),
+    ];
+    if !notes.is_empty() {
+        blocks.push(Block::section(notes.clone()));
+    }
+    blocks.push(Block::divider());
+    blocks.push(
+        SectionBlock::fields_only(vec![block_field("Tag", &tag), block_field("Pre-release", prerelease)])
+            .accessory(Element::link_button("リリースを見る", "open_release", &rel.html_url))
+            .into(),
+    );
+
+    let mut att_fields = vec![
+        slack::AttachmentField {
+            title: "Tag",
+            value: tag,
+            short: true,
+        },
+        slack::AttachmentField {
+            title: "Pre-release",
+            value: String::from(prerelease),
+            short: true,
+        },
+    ];
+    if !rel.assets.is_empty() {
+        let assets = rel
+            .assets
+            .iter()
+            .map(|a| format!("<{}|{}> ({})", a.browser_download_url, a.name, format_byte_size(a.size)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        blocks.push(Block::section(format!("*Assets*\n{assets}")));
+        att_fields.push(slack::AttachmentField {
+            title: "Assets",
+            value: assets,
+            short: false,
+        });
+    }
+
+    let attachment = slack::Attachment::new(&notes)
+        .author(&rel.author.login, &rel.author.html_url, &rel.author.avatar_url)
+        .title(&title, &rel.html_url)
+        .color(if rel.prerelease {
+            COLOR_PRERELEASE
+        } else {
+            COLOR_RELEASE
+        })
+        .fields(att_fields)
+        .blocks(blocks);
+
+    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
+        .await
+}
+
+fn truncate_text(text: &str, max_chars: usize) -> Cow<'_, str> {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+// GitHub Flavored MarkdownのうちSlackで崩れる記法(見出し・太字・リンク)だけmrkdwnに書き換える
+fn markdown_to_mrkdwn(md: &str) -> String {
+    md.lines()
+        .map(|l| {
+            let l = match l.trim_start_matches('#') {
+                h if h.len() != l.len() && h.starts_with(' ') => format!("*{}*", h.trim()),
+                _ => l.replace("**", "*"),
+            };
+
+            replace_markdown_links(&l)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn replace_markdown_links(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some((label, after_label)) = rest[open + 1..].split_once("](") else {
+            break;
+        };
+        let Some((url, after_url)) = after_label.split_once(')') else {
+            break;
+        };
+        if label.contains('[') || url.contains(char::is_whitespace) {
+            out.push_str(&rest[..open + 1]);
+            rest = &rest[open + 1..];
+            continue;
+        }
+
+        out.push_str(&rest[..open]);
+        out.push_str(&format!("<{url}|{label}>"));
+        rest = after_url;
+    }
+    out.push_str(rest);
+
+    out
+}
+
+fn format_byte_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+// pushイベントの通知に列挙するコミットの最大数(これを超えた分はcompareへのリンクにまとめる)
+const PUSH_COMMIT_LIST_LIMIT: usize = 10;
+
+async fn process_push_event(ctx: ExecutionContext, event: github::PushEvent<'_>) -> Result<(), Error> {
+    let git_ref = event.git_ref;
+    let commits = event.commits;
+    let repo = event.repository;
+    let sender = event.sender;
+
+    let git_ref = github::GitRef::parse(&git_ref);
+    let ref_kind = match git_ref {
+        github::GitRef::Branch(_) => "ブランチ",
+        github::GitRef::Tag(_) => "タグ",
+        github::GitRef::Other(_) => "ref",
+    };
+    let ref_name = git_ref.name();
+
+    let msg = if event.deleted {
+        format!("*{}さん* が{ref_kind} `{ref_name}` を削除したよ", sender.login)
+    } else if event.created {
+        format!("*{}さん* が{ref_kind} `{ref_name}` を作成したよ！", sender.login)
+    } else if event.forced {
+        format!(
+            ":warning: *{}さん* が{ref_kind} `{ref_name}` に強制pushしたよ！ :warning:",
+            sender.login
+        )
+    } else {
+        format!(
+            "*{}さん* が{ref_kind} `{ref_name}` に{}件のコミットをpushしたよ！",
+            sender.login,
+            commits.len()
+        )
+    };
+
+    let mut commit_lines = commits
+        .iter()
+        .take(PUSH_COMMIT_LIST_LIMIT)
+        .map(|c| {
+            format!(
+                "<{}|`{}`> {} - {}",
+                github::commit_html_url(&repo, c.id),
+                &c.id[..7.min(c.id.len())],
+                c.message_headline(),
+                c.author.username.as_deref().unwrap_or(&c.author.name)
+            )
+        })
+        .collect::<Vec<_>>();
+    if commits.len() > PUSH_COMMIT_LIST_LIMIT {
+        let rest = commits.len() - PUSH_COMMIT_LIST_LIMIT;
+        commit_lines.push(match event.compare {
+            Some(ref c) => format!("<{c}|…ほか{rest}件のコミット>"),
+            None => format!("…ほか{rest}件のコミット"),
+        });
+    }
+    let text = commit_lines.join("\n");
+
+    let title = format!("[{}] {ref_name}", repo.full_name);
+    let title_link = match event.compare {
+        Some(ref c) if !event.deleted => c as &str,
+        _ => &repo.html_url,
+    };
+    let mut summary = SectionBlock::mrkdwn(format!("*<{title_link}|{title}>*"));
+    if let Some(c) = event.compare.as_deref().filter(|_| !event.deleted) {
+        summary = summary.accessory(Element::link_button("差分を見る", "open_compare", c));
+    }
+    let mut blocks = vec![summary.into()];
+    if !text.is_empty() {
+        blocks.push(Block::section(text.clone()));
+    }
+    blocks.push(author_context(&sender.login, &sender.html_url, &sender.avatar_url));
+    let attachment = slack::Attachment::new(&text)
+        .author(&sender.login, &sender.html_url, &sender.avatar_url)
+        .title(&title, title_link)
+        .color(match (event.deleted, event.forced) {
+            (true, _) => COLOR_CLOSED,
+            (false, true) => COLOR_FORCE_PUSH,
+            (false, false) => COLOR_PUSH,
+        })
+        .blocks(blocks);
+
+    ctx.post_message(&msg, |x| x.as_user().attachments(vec![attachment]))
+        .await
+}
+
+async fn process_workflow_run_event(
+    ctx: ExecutionContext,
+    action: github::Action,
+    run: github::WorkflowRun,
+    repository: github::Repository<'_>,
+) -> Result<(), Error> {
+    if action != github::Action::Completed {
+        // requested/in_progressは完了時にまとめて通知する
+        tracing::trace!("skipping workflow_run action: {action:?}");
+        return Ok(());
+    }
+    let conclusion = run
+        .conclusion
+        .ok_or(ProcessError::RequireField("workflow_run.conclusion"))?;
+
+    let apiclient = ctx.connect_github(&repository.full_name).await?;
+    let recovered = if conclusion == github::WorkflowConclusion::Success {
+        apiclient
+            .previous_workflow_run_conclusion(run.workflow_id, &run.head_branch, run.run_number)
+            .await?
+            .is_some_and(|c| c.is_failure())
+    } else {
+        false
+    };
+    if ctx.route.workflow_run_notify == WorkflowRunNotifyMode::FailuresAndRecoveries
+        && !conclusion.is_failure()
+        && !recovered
+    {
+        return Ok(());
+    }
+
+    let failed_jobs = if conclusion.is_failure() {
+        apiclient
+            .list_workflow_run_jobs(run.id)
+            .await?
+            .jobs
+            .into_iter()
+            .filter(|j| j.conclusion.is_some_and(|c| c.is_failure()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let (msg, color) = match conclusion {
+        github::WorkflowConclusion::Success if recovered => (
+            format!(
+                ":white_check_mark: ワークフロー *{}* が復旧したよ！ :white_check_mark:",
+                run.name
+            ),
+            COLOR_WORKFLOW_SUCCESS,
+        ),
+        github::WorkflowConclusion::Success => (
+            format!(":white_check_mark: ワークフロー *{}* が成功したよ！", run.name),
+            COLOR_WORKFLOW_SUCCESS,
+        ),
+        github::WorkflowConclusion::Failure | github::WorkflowConclusion::StartupFailure => (
+            format!(":x: ワークフロー *{}* が失敗しちゃった… :x:", run.name),
+            COLOR_WORKFLOW_FAILURE,
+        ),
+        github::WorkflowConclusion::TimedOut => (
+            format!(
+                ":hourglass: ワークフロー *{}* がタイムアウトしちゃった… :hourglass:",
+                run.name
+            ),
+            COLOR_WORKFLOW_FAILURE,
+        ),
+        github::WorkflowConclusion::Cancelled => (
+            format!("ワークフロー *{}* がキャンセルされたよ", run.name),
+            COLOR_WORKFLOW_NEUTRAL,
+        ),
+        c => (
+            format!("ワークフロー *{}* が終了したよ ({c:?})", run.name),
+            COLOR_WORKFLOW_NEUTRAL,
+        ),
+    };
+
+    let actor = run.triggering_actor.as_ref().unwrap_or(&run.actor);
+    let mut att_fields = vec![
+        slack::AttachmentField {
+            title: "Conclusion",
+            value: format!("{conclusion:?}"),
+            short: true,
+        },
+        slack::AttachmentField {
+            title: "実行時間",
+            value: run.duration().map_or_else(|| String::from("?"), format_duration),
+            short: true,
+        },
+        slack::AttachmentField {
+            title: "ブランチ",
+            value: format!(
+                "`{}` (<{}|{}>)",
+                run.head_branch,
+                github::commit_html_url(&repository, &run.head_sha),
+                &run.head_sha[..8.min(run.head_sha.len())]
+            ),
+            short: true,
+        },
+        slack::AttachmentField {
+            title: "実行者",
+            value: format!("<{}|{}>", actor.html_url, actor.login),
+            short: true,
+        },
+    ];
+    let title = format!("[{}] {} #{}", repository.full_name, run.name, run.run_number);
+    let mut blocks = vec![SectionBlock::mrkdwn(format!("*<{}|{title}>*", run.html_url))
+        .fields(att_fields.iter().map(|f| block_field(f.title, &f.value)).collect())
+        .accessory(Element::link_button("ログを見る", "open_workflow_run", &run.html_url))
+        .into()];
+    if !failed_jobs.is_empty() {
+        let jobs = failed_jobs
+            .iter()
+            .map(|j| format!("<{}|{}>", j.html_url, j.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        blocks.push(Block::section(format!("*失敗したジョブ*\n{jobs}")));
+        att_fields.push(slack::AttachmentField {
+            title: "失敗したジョブ",
+            value: jobs,
+            short: false,
+        });
+    }
+    blocks.push(author_context(&actor.login, &actor.html_url, &actor.avatar_url));
+
+    let attachment = slack::Attachment::new("")
+        .author(&actor.login, &actor.html_url, &actor.avatar_url)
+        .title(&title, &run.html_url)
+        .color(color)
+        .fields(att_fields)
+        .blocks(blocks);
+
+    ctx.post_message(&msg, |p| p.as_user().attachments(vec![attachment]))
+        .await
+}
+
+fn format_duration(d: time::Duration) -> String {
+    let secs = d.whole_seconds().max(0);
+
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}秒"),
+        (0, m, s) => format!("{m}分{s}秒"),
+        (h, m, s) => format!("{h}時間{m}分{s}秒"),
+    }
 }
 
 fn detect_branch_flow(head: &str, base: &str) -> &'static str {
@@ -4,6 +4,9 @@ pub struct Secrets {
     pub github_app_id: String,
     pub github_app_installation_id: String,
     pub github_webhook_verification_secret: String,
+    /// ローテーション中に併せて受け付ける共通のシークレット
+    #[serde(default)]
+    pub github_webhook_previous_secrets: Vec<String>,
     pub github_app_pem: String,
 }
 impl Secrets {
@@ -14,8 +17,39 @@ impl Secrets {
             .send()
             .await?
             .secret_string
-            .expect("No secret string?");
+            .ok_or("No secret string?")?;
 
         serde_json::from_str(&data).map_err(From::from)
     }
+
+    /// 共通のWebhookシークレットとして有効なものすべて
+    pub fn webhook_verification_secrets(&self) -> Vec<String> {
+        std::iter::once(&self.github_webhook_verification_secret)
+            .chain(&self.github_webhook_previous_secrets)
+            .cloned()
+            .collect()
+    }
+}
+
+/// ルート専用のWebhookシークレット(平文のSecretString)を読み込む
+///
+/// ローテーション中は1行に1つずつ複数のシークレットを置いておけば、どれでも受け付ける
+pub async fn load_route_secrets(
+    config: &aws_config::SdkConfig,
+    secret_arn: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let data = aws_sdk_secretsmanager::Client::new(config)
+        .get_secret_value()
+        .secret_id(secret_arn)
+        .send()
+        .await?
+        .secret_string
+        .ok_or("Route secret has no SecretString")?;
+
+    Ok(data
+        .lines()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect())
 }